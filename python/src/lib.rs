@@ -0,0 +1,417 @@
+//! Python bindings for scripting experiments, including a Gym-style environment.
+//!
+//! Build and install into the current virtualenv with `maturin develop` in this directory:
+//!
+//! ```text
+//! import pyblokus
+//! env = pyblokus.BlokusEnv(size=14, colors=2, opponent="greedy")
+//! obs, info = env.reset(seed=1)
+//! obs, reward, terminated, truncated, info = env.step(env.legal_actions()[0])
+//! ```
+//!
+//! A move is a `(piece, variation, offset)` tuple as in `blokus::board::Move`.
+
+extern crate blokus;
+extern crate pyo3;
+extern crate rand;
+
+use std::collections::HashMap;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+use blokus::agent::parse_agent;
+use blokus::board::{self, Color, Move};
+use blokus::encoding::{self, Perspective};
+use blokus::game::{self, Game};
+use blokus::pieces::{self, Piece, Point};
+use blokus::protocol::board_to_text;
+use blokus::treesearch::GameStateMut;
+
+fn value_error<E: ToString>(e: E) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+/// A piece given by a script must have distinct points with non-negative
+/// coordinates, so that its rotations and reflections stay within range.
+fn check_piece(points: &[Point]) -> PyResult<()> {
+    if points.is_empty() {
+        return Err(value_error("a piece needs at least one point"));
+    }
+    if points.iter().any(|&(x, y)| x < 0 || y < 0) {
+        return Err(value_error(format!("piece {:?} has a negative coordinate", points)));
+    }
+    if points.iter().enumerate().any(|(i, p)| points[..i].contains(p)) {
+        return Err(value_error(format!("piece {:?} repeats a point", points)));
+    }
+    Ok(())
+}
+
+#[pyclass(name = "Board")]
+#[derive(Clone)]
+pub struct PyBoard {
+    board: board::Board,
+}
+
+#[pymethods]
+impl PyBoard {
+    /// A board where color `c` starts at `homes[c]`;
+    /// `pieces` are lists of `(x, y)` points as returned by `compute_pieces`.
+    #[new]
+    fn new(pieces: Vec<Vec<Point>>, rows: usize, cols: usize, homes: Vec<Point>) -> PyResult<Self> {
+        if homes.is_empty() {
+            return Err(value_error("expected at least one home"));
+        }
+        for points in pieces.iter() {
+            check_piece(points)?;
+        }
+        let pieces = pieces.into_iter().map(|points| Piece { points }).collect();
+        Ok(PyBoard { board: board::Board::new(pieces, rows, cols, &homes).map_err(value_error)? })
+    }
+
+    fn size(&self) -> usize {
+        self.board.size()
+    }
+
+    #[getter]
+    fn rows(&self) -> usize {
+        self.board.rows()
+    }
+
+    #[getter]
+    fn cols(&self) -> usize {
+        self.board.cols()
+    }
+
+    #[getter]
+    fn colors(&self) -> usize {
+        self.board.colors()
+    }
+
+    /// The color covering cell `i`, or `None`.
+    fn at(&self, i: usize) -> PyResult<Option<Color>> {
+        if i >= self.board.size() {
+            return Err(value_error("cell outside the board"));
+        }
+        Ok(self.board.at(i))
+    }
+
+    /// Legal moves of `color`.
+    fn moves(&self, color: Color) -> PyResult<Vec<Move>> {
+        self.board.legal_moves(color).map_err(value_error)
+    }
+
+    /// The cells covered by a legal move of `color`.
+    fn cells(&self, color: Color, mv: Move) -> PyResult<Vec<usize>> {
+        if !self.moves(color)?.contains(&mv) {
+            return Err(value_error("not a legal move"));
+        }
+        Ok(self.board.cells(mv))
+    }
+
+    fn place(&mut self, color: Color, mv: Move) -> PyResult<()> {
+        self.board.place(color, mv).map_err(value_error)
+    }
+
+    fn undo(&mut self) -> PyResult<()> {
+        self.board.undo().map_err(value_error)
+    }
+
+    fn remaining_squares(&self, color: Color) -> PyResult<usize> {
+        if color as usize >= self.board.colors() {
+            return Err(value_error("invalid color"));
+        }
+        Ok(self.board.remaining_squares(color))
+    }
+
+    fn __str__(&self) -> String {
+        board_to_text(&self.board)
+    }
+}
+
+/// All pieces of up to `max_size` squares, as lists of `(x, y)` points.
+#[pyfunction]
+fn compute_pieces(max_size: usize) -> PyResult<Vec<Vec<Point>>> {
+    if max_size == 0 {
+        return Err(value_error("pieces have at least one square"));
+    }
+    Ok(pieces::compute_pieces(max_size).into_iter().map(|p| p.points).collect())
+}
+
+/// Standard Blokus score of `color`.
+#[pyfunction]
+fn score(board: PyRef<'_, PyBoard>, color: Color) -> PyResult<i32> {
+    if color as usize >= board.board.colors() {
+        return Err(value_error("invalid color"));
+    }
+    Ok(game::score(&board.board, color))
+}
+
+#[pyclass(name = "Game")]
+#[derive(Clone)]
+pub struct PyGame {
+    game: Game,
+}
+
+#[pymethods]
+impl PyGame {
+    /// A game where the colors start in the corners of a `size` by `size` board.
+    #[new]
+    #[pyo3(signature = (size=20, colors=4, pieces=5))]
+    fn new(size: usize, colors: usize, pieces: usize) -> PyResult<Self> {
+        Ok(PyGame { game: Game::standard(size, colors, pieces).map_err(value_error)? })
+    }
+
+    /// A copy of the current position.
+    #[getter]
+    fn board(&self) -> PyBoard {
+        PyBoard { board: self.game.board().clone() }
+    }
+
+    /// The color to move, or `None` when the game is over.
+    #[pyo3(name = "to_move")]
+    fn color_to_move(&mut self) -> Option<Color> {
+        if self.game.move_count() == 0 { None } else { Some(self.game.to_move()) }
+    }
+
+    fn legal_moves(&mut self) -> Vec<Move> {
+        self.game.legal_moves().to_vec()
+    }
+
+    fn must_pass(&mut self) -> bool {
+        self.game.must_pass()
+    }
+
+    /// Play a legal move, or pass with `None`.
+    #[pyo3(signature = (mv))]
+    fn play(&mut self, mv: Option<Move>) -> PyResult<()> {
+        if self.game.move_count() == 0 {
+            return Err(value_error("the game is over"));
+        }
+        self.game.play(mv).map_err(value_error)
+    }
+
+    /// Take back the last move; false if there is nothing to take back.
+    fn take_back(&mut self) -> bool {
+        self.game.take_back().is_some()
+    }
+
+    fn history(&self) -> Vec<Option<Move>> {
+        self.game.history().to_vec()
+    }
+
+    fn scores(&self) -> Vec<i32> {
+        self.game.scores()
+    }
+
+    /// Let an agent (see `blokus::agent::parse_agent`) choose a move
+    /// for the color to move without playing it.
+    #[pyo3(signature = (agent="greedy", seed=0))]
+    fn choose_move(&mut self, agent: &str, seed: u64) -> PyResult<Option<Move>> {
+        let mut agent = parse_agent(agent).map_err(value_error)?;
+        if self.game.move_count() == 0 {
+            return Err(value_error("the game is over"));
+        }
+        agent.check(self.game.board()).map_err(value_error)?;
+        Ok(agent.choose(&mut self.game, &mut StdRng::seed_from_u64(seed)))
+    }
+}
+
+type Observation = Vec<Vec<Vec<f32>>>;
+type Info = HashMap<&'static str, Vec<i32>>;
+
+/// A single-agent environment in the style of Gym: the agent plays color 0
+/// and the other colors are played by `opponent`.
+///
+/// Actions and observations are encoded as in `blokus::encoding`, so the action
+/// space has `action_count` actions of which `legal_actions()` are legal.
+/// Positions where color 0 has to pass are skipped. The reward is 1 for
+/// winning, -1 for losing and 0 for a draw, given when the game is over.
+#[pyclass(name = "BlokusEnv")]
+pub struct Env {
+    game: Game,
+    size: usize,
+    colors: usize,
+    piece_size: usize,
+    opponent: String,
+    rng: StdRng,
+}
+
+impl Env {
+    /// Let the opponents move, and pass for the agent, until the agent
+    /// has a move or the game is over.
+    fn advance(&mut self) {
+        let mut opponent = parse_agent(&self.opponent).unwrap();
+        while self.game.move_count() > 0 && (self.game.to_move() != 0 || self.game.must_pass()) {
+            let mv = if self.game.to_move() == 0 { None } else { opponent.choose(&mut self.game, &mut self.rng) };
+            self.game.play(mv).unwrap();
+        }
+    }
+
+    fn perspective(&self) -> Perspective {
+        Perspective::new(self.game.board(), 0, true)
+    }
+
+    fn info(&self) -> Info {
+        let mut info = HashMap::new();
+        info.insert("scores", self.game.scores());
+        info
+    }
+}
+
+#[pymethods]
+impl Env {
+    #[new]
+    #[pyo3(signature = (size=14, colors=2, pieces=5, opponent="random", seed=0))]
+    fn new(size: usize, colors: usize, pieces: usize, opponent: &str, seed: u64) -> PyResult<Self> {
+        let game = Game::standard(size, colors, pieces).map_err(value_error)?;
+        parse_agent(opponent).and_then(|a| a.check(game.board())).map_err(value_error)?;
+        Ok(Env {
+            game,
+            size,
+            colors,
+            piece_size: pieces,
+            opponent: opponent.to_string(),
+            rng: StdRng::seed_from_u64(seed),
+        })
+    }
+
+    /// `(planes, rows, cols)` as in `blokus::encoding::observation_shape`.
+    #[getter]
+    fn observation_shape(&self) -> (usize, usize, usize) {
+        encoding::observation_shape(self.game.board())
+    }
+
+    #[getter]
+    fn action_count(&self) -> usize {
+        encoding::action_count(self.game.board())
+    }
+
+    #[pyo3(signature = (seed=None))]
+    fn reset(&mut self, seed: Option<u64>) -> (Observation, Info) {
+        if let Some(seed) = seed {
+            self.rng = StdRng::seed_from_u64(seed);
+        }
+        self.game = Game::standard(self.size, self.colors, self.piece_size).unwrap();
+        self.advance();
+        (self.observation(), self.info())
+    }
+
+    /// Play the given action, then let the opponents move.
+    /// Returns `(observation, reward, terminated, truncated, info)`.
+    fn step(&mut self, action: usize) -> PyResult<(Observation, f64, bool, bool, Info)> {
+        if self.game.move_count() == 0 {
+            return Err(value_error("the episode is over; call reset()"));
+        }
+        let mv = match self.perspective().decode(self.game.board(), action).map_err(value_error)? {
+            Some(mv) if self.game.legal_moves().contains(&mv) => mv,
+            _ => return Err(value_error("not a legal action")),
+        };
+        self.game.play(Some(mv)).map_err(value_error)?;
+        self.advance();
+        let terminated = self.game.move_count() == 0;
+        let mut reward = 0.0;
+        if terminated {
+            reward = game::outcomes(&self.game.scores())[0];
+        }
+        Ok((self.observation(), reward, terminated, false, self.info()))
+    }
+
+    /// One flag per action; all false once the episode is over.
+    fn legal_action_mask(&mut self) -> Vec<bool> {
+        if self.game.move_count() == 0 {
+            return vec![false; self.action_count()];
+        }
+        self.perspective().legal_mask(self.game.board())
+    }
+
+    fn legal_actions(&mut self) -> Vec<usize> {
+        self.legal_action_mask().iter().enumerate().filter(|&(_, &legal)| legal).map(|(a, _)| a).collect()
+    }
+
+    /// The move of color 0 behind an action.
+    fn action_to_move(&self, action: usize) -> PyResult<Option<Move>> {
+        self.perspective().decode(self.game.board(), action).map_err(value_error)
+    }
+
+    fn observation(&self) -> Observation {
+        let (planes, rows, cols) = self.observation_shape();
+        let flat = self.perspective().observation(self.game.board());
+        (0..planes).map(|p| (0..rows).map(|y| {
+            let start = (p * rows + y) * cols;
+            flat[start..start + cols].to_vec()
+        }).collect()).collect()
+    }
+
+    /// The current game, for inspecting the moves behind the actions.
+    #[getter]
+    fn game(&self) -> PyGame {
+        PyGame { game: self.game.clone() }
+    }
+
+    fn render(&self) -> String {
+        board_to_text(self.game.board())
+    }
+}
+
+#[pymodule]
+fn pyblokus(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyBoard>()?;
+    m.add_class::<PyGame>()?;
+    m.add_class::<Env>()?;
+    m.add_function(wrap_pyfunction!(compute_pieces, m)?)?;
+    m.add_function(wrap_pyfunction!(score, m)?)?;
+    Ok(())
+}
+
+#[test]
+fn test_board() {
+    let pieces = compute_pieces(2).unwrap();
+    assert_eq!(pieces, vec![vec![(0, 0)], vec![(0, 0), (0, 1)]]);
+    let mut b = PyBoard::new(pieces, 3, 3, vec![(0, 0), (2, 2)]).unwrap();
+    assert!(PyBoard::new(Vec::new(), 3, 3, vec![(3, 0)]).is_err());
+    assert!(PyBoard::new(vec![vec![]], 3, 3, vec![(0, 0)]).is_err());
+    assert!(PyBoard::new(vec![vec![(-128, 0)]], 3, 3, vec![(0, 0)]).is_err());
+    assert!(PyBoard::new(vec![vec![(0, 0), (0, 0)]], 3, 3, vec![(0, 0)]).is_err());
+    let moves = b.moves(0).unwrap();
+    assert!(b.place(1, moves[0]).is_err());
+    b.place(0, moves[0]).unwrap();
+    assert_eq!(b.at(0).unwrap(), Some(0));
+    assert!(b.at(9).is_err());
+    b.undo().unwrap();
+    assert!(b.undo().is_err());
+    assert!(b.moves(2).is_err());
+}
+
+#[test]
+fn test_env_episode() {
+    let mut env = Env::new(7, 2, 3, "greedy", 3).unwrap();
+    let (obs, _) = env.reset(None);
+    assert_eq!((obs.len(), obs[0].len(), obs[0][0].len()), env.observation_shape());
+    // The corner plane of color 0 has its home.
+    assert_eq!(obs[2][0][0], 1.0);
+    assert!(env.step(env.action_count() - 1).is_err());
+    assert!(env.step(env.action_count()).is_err());
+    let mut steps = 0;
+    loop {
+        let actions = env.legal_actions();
+        assert!(!actions.is_empty());
+        let (obs, reward, terminated, truncated, info) = env.step(actions[actions.len() / 2]).unwrap();
+        steps += 1;
+        assert!(!truncated);
+        // Pieces of up to three squares have 1 + 2 + 3 + 3 = 9 squares.
+        let placed = 9 - env.game.board().remaining_squares(0);
+        assert_eq!(obs[0].iter().flatten().sum::<f32>() as usize, placed);
+        assert_eq!(info["scores"].len(), 2);
+        if terminated {
+            assert!(reward == 1.0 || reward == -1.0 || reward == 0.0);
+            break;
+        }
+        assert_eq!(reward, 0.0);
+    }
+    assert!(steps >= 2);
+    assert!(env.legal_actions().is_empty());
+    assert!(env.step(0).is_err());
+}
@@ -0,0 +1,259 @@
+use std::time::Duration;
+
+use rand::{Rng, RngCore};
+
+use board::{Board, Move};
+use eval::{WeightedEvaluator, greedy_move};
+use game::Game;
+use mcts::MctsSearch;
+use minimax::{Algorithm, SearchLimits, search};
+use nn::ConvNet;
+use puct::{Evaluator, PuctConfig, PuctSearch, UniformEvaluator};
+use treesearch::GameStateMut;
+
+/// A bot that plays competitive games.
+pub trait Agent {
+    /// Short description, as accepted by `parse_agent`.
+    fn name(&self) -> String;
+    /// Choose a legal move for the color to move in `game`, or `None` to pass.
+    /// The game is in the same position when the method returns.
+    fn choose(&mut self, game: &mut Game, rng: &mut dyn RngCore) -> Option<Move>;
+    /// Whether the agent can play on `board`; call it before a game starts,
+    /// since `choose` may panic on boards that fail the check.
+    fn check(&self, _board: &Board) -> Result<(), String> {
+        Ok(())
+    }
+    /// The time limit per move, for agents that have one.
+    fn time_limit(&mut self) -> Option<&mut Duration> {
+        None
+    }
+}
+
+/// Plays a uniformly random legal move.
+pub struct RandomAgent;
+
+impl Agent for RandomAgent {
+    fn name(&self) -> String {
+        "random".to_string()
+    }
+
+    fn choose(&mut self, game: &mut Game, rng: &mut dyn RngCore) -> Option<Move> {
+        let moves = game.legal_moves();
+        if moves.is_empty() {
+            None
+        } else {
+            Some(moves[rng.gen_range(0, moves.len())])
+        }
+    }
+}
+
+/// Plays the move that maximizes the evaluator after one ply.
+pub struct GreedyAgent {
+    pub evaluator: WeightedEvaluator,
+}
+
+impl Agent for GreedyAgent {
+    fn name(&self) -> String {
+        "greedy".to_string()
+    }
+
+    fn choose(&mut self, game: &mut Game, _rng: &mut dyn RngCore) -> Option<Move> {
+        let color = game.to_move();
+        let mut board = game.board().clone();
+        greedy_move(&mut board, color, &self.evaluator)
+    }
+}
+
+/// Monte Carlo tree search of `mcts` with a fixed number of iterations,
+/// where every color plays for itself.
+/// The tree of the previous move is reused when the game has continued from it.
+pub struct MctsAgent {
+    pub iterations: usize,
+    /// Weight of the exploration term of the UCT rule.
+    pub exploration: f64,
+    /// The search after the last move, with the game so far.
+    search: Option<(MctsSearch, Vec<Option<Move>>)>,
+}
+
+impl MctsAgent {
+    pub fn new(iterations: usize, exploration: f64) -> Self {
+        MctsAgent { iterations, exploration, search: None }
+    }
+
+    /// The previous search moved along the moves played since, or a new search.
+    fn reuse_tree(&mut self, game: &Game) -> MctsSearch {
+        let exploration = self.exploration;
+        let fresh = || MctsSearch::new(game, exploration);
+        let (mut search, history) = match self.search.take() {
+            Some(s) => s,
+            None => return fresh(),
+        };
+        if !game.history().starts_with(&history) {
+            return fresh();
+        }
+        for &mv in game.history()[history.len()..].iter() {
+            search = match search.reroot(mv) {
+                Some(s) => s,
+                None => return fresh(),
+            };
+        }
+        search
+    }
+}
+
+impl Agent for MctsAgent {
+    fn name(&self) -> String {
+        format!("mcts:{}", self.iterations)
+    }
+
+    fn choose(&mut self, game: &mut Game, rng: &mut dyn RngCore) -> Option<Move> {
+        if game.move_count() == 0 {
+            return None;
+        }
+        let mut search = self.reuse_tree(game);
+        search.run(game, rng, self.iterations.max(1));
+        let mv = search.best_move()?;
+        let mut history = game.history().to_vec();
+        history.push(mv);
+        self.search = search.reroot(mv).map(|s| (s, history));
+        mv
+    }
+}
+
+/// Paranoid or Max^n search with a time limit per move.
+pub struct SearchAgent {
+    pub algorithm: Algorithm,
+    pub time_limit: Duration,
+}
+
+impl Agent for SearchAgent {
+    fn name(&self) -> String {
+        let name = match self.algorithm {
+            Algorithm::Paranoid => "paranoid",
+            Algorithm::MaxN => "maxn",
+        };
+        format!("{}:{}", name, self.time_limit.as_secs() * 1000 + self.time_limit.subsec_millis() as u64)
+    }
+
+    fn choose(&mut self, game: &mut Game, _rng: &mut dyn RngCore) -> Option<Move> {
+        if game.move_count() == 0 {
+            return None;
+        }
+        let limits = SearchLimits { max_depth: 64, time_limit: Some(self.time_limit) };
+        let i = search(game, self.algorithm, limits).best_move?;
+        game.move_at(i)
+    }
+
+    fn time_limit(&mut self) -> Option<&mut Duration> {
+        Some(&mut self.time_limit)
+    }
+}
+
+/// Evaluator-guided tree search with a fixed number of iterations.
+pub struct PuctAgent {
+    pub iterations: usize,
+    pub evaluator: Box<dyn Evaluator>,
+    /// The weights file of the evaluator, if it is a network.
+    pub weights: Option<String>,
+    pub config: PuctConfig,
+}
+
+impl Agent for PuctAgent {
+    fn name(&self) -> String {
+        match self.weights {
+            Some(ref path) => format!("puct:{}:{}", self.iterations, path),
+            None => format!("puct:{}", self.iterations),
+        }
+    }
+
+    fn choose(&mut self, game: &mut Game, _rng: &mut dyn RngCore) -> Option<Move> {
+        if game.move_count() == 0 {
+            return None;
+        }
+        let mut search = PuctSearch::new(game, self.config.clone());
+        search.run(game, &mut self.evaluator, self.iterations.max(1));
+        search.best_move()?
+    }
+
+    fn check(&self, board: &Board) -> Result<(), String> {
+        self.evaluator.check(board)
+    }
+}
+
+fn parse_puct(spec: &str, arg: &str) -> Result<Box<dyn Agent>, String> {
+    let mut parts = arg.splitn(2, ':');
+    let iterations = parts.next().unwrap().parse::<usize>().map_err(|_| format!("invalid number in agent {:?}", spec))?;
+    let weights = parts.next().map(|path| path.to_string());
+    let evaluator: Box<dyn Evaluator> = match weights {
+        Some(ref path) => Box::new(ConvNet::load(path).map_err(|e| format!("cannot load {}: {}", path, e))?),
+        None => Box::new(UniformEvaluator),
+    };
+    Ok(Box::new(PuctAgent { iterations, evaluator, weights, config: PuctConfig::default() }))
+}
+
+/// Parse an agent description: `random`, `greedy`, `mcts:ITERATIONS`,
+/// `paranoid:MILLISECONDS`, `maxn:MILLISECONDS` or `puct:ITERATIONS[:WEIGHTS]`,
+/// where `WEIGHTS` is a file for `nn::ConvNet`.
+pub fn parse_agent(spec: &str) -> Result<Box<dyn Agent>, String> {
+    let mut parts = spec.splitn(2, ':');
+    let name = parts.next().unwrap();
+    if name == "puct" {
+        return match parts.next() {
+            Some(arg) => parse_puct(spec, arg),
+            None => Err(format!("unknown agent {:?}", spec)),
+        };
+    }
+    let arg = parts.next().map(|a| a.parse::<u64>().map_err(|_| format!("invalid number in agent {:?}", spec)));
+    match (name, arg) {
+        ("random", None) => Ok(Box::new(RandomAgent)),
+        ("greedy", None) => Ok(Box::new(GreedyAgent { evaluator: WeightedEvaluator::default() })),
+        ("mcts", Some(n)) => Ok(Box::new(MctsAgent::new(n? as usize, 1.0))),
+        ("paranoid", Some(ms)) => Ok(Box::new(SearchAgent { algorithm: Algorithm::Paranoid, time_limit: Duration::from_millis(ms?) })),
+        ("maxn", Some(ms)) => Ok(Box::new(SearchAgent { algorithm: Algorithm::MaxN, time_limit: Duration::from_millis(ms?) })),
+        _ => Err(format!("unknown agent {:?}", spec)),
+    }
+}
+
+#[test]
+fn test_agents_play_legal_moves() {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    let mut rng = StdRng::seed_from_u64(1);
+    for spec in &["random", "greedy", "mcts:20", "paranoid:10", "maxn:10", "puct:20"] {
+        let mut agent = parse_agent(spec).unwrap();
+        assert_eq!(&agent.name(), spec);
+        let mut game = Game::new(Board::new(::pieces::compute_pieces(3), 6, 6, &[(0, 0), (5, 5)]).unwrap());
+        while game.move_count() > 0 {
+            let mv = agent.choose(&mut game, &mut rng);
+            match mv {
+                Some(mv) => assert!(game.legal_moves().contains(&mv)),
+                None => assert!(game.must_pass()),
+            }
+            game.play(mv).unwrap();
+        }
+    }
+    assert!(parse_agent("mcts").is_err());
+
+    // The tree is carried over to the next move of the same color.
+    let mut agent = MctsAgent::new(50, 1.0);
+    let mut game = Game::new(Board::new(::pieces::compute_pieces(3), 6, 6, &[(0, 0), (5, 5)]).unwrap());
+    let mv = agent.choose(&mut game, &mut rng);
+    game.play(mv).unwrap();
+    let reply = game.legal_moves()[0];
+    game.play(Some(reply)).unwrap();
+    let search = agent.reuse_tree(&game);
+    assert!(search.visits() > 0);
+    assert_eq!(game.history(), &[mv, Some(reply)]);
+    assert!(parse_agent("random:3").is_err());
+    assert!(parse_agent("paranoid:x").is_err());
+    assert!(parse_agent("puct").is_err());
+    assert!(parse_agent("puct:10:/nonexistent/weights").is_err());
+
+    // A network for one board does not fit another.
+    let small = Board::new(::pieces::compute_pieces(3), 6, 6, &[(0, 0), (5, 5)]).unwrap();
+    let large = Board::new(::pieces::compute_pieces(4), 6, 6, &[(0, 0), (5, 5)]).unwrap();
+    let evaluator = Box::new(ConvNet::random(&small, 2, 1, &mut rng));
+    let agent = PuctAgent { iterations: 10, evaluator, weights: None, config: PuctConfig::default() };
+    assert!(agent.check(&small).is_ok());
+    assert!(agent.check(&large).is_err());
+}
@@ -0,0 +1,19 @@
+extern crate blokus;
+extern crate rand;
+
+use std::io;
+
+use rand::FromEntropy;
+use rand::rngs::StdRng;
+
+use blokus::protocol::Engine;
+
+/// Speak the text protocol of `blokus::protocol` on stdin and stdout.
+fn main() {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut engine = Engine::new(StdRng::from_entropy());
+    if let Err(e) = engine.run(stdin.lock(), stdout.lock()) {
+        eprintln!("{}", e);
+    }
+}
@@ -0,0 +1,134 @@
+extern crate blokus;
+extern crate rand;
+
+use std::env;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::process;
+
+use rand::{SeedableRng, thread_rng, Rng};
+use rand::rngs::StdRng;
+
+use blokus::nn::ConvNet;
+use blokus::puct::{Evaluator, UniformEvaluator};
+use blokus::selfplay::{Header, Reader, SelfPlayConfig, play_game, write_game, write_header};
+
+struct Config {
+    games: usize,
+    header: Header,
+    selfplay: SelfPlayConfig,
+    weights: Option<String>,
+    seed: Option<u64>,
+    output: Option<String>,
+    read: Option<String>,
+}
+
+fn usage() -> ! {
+    eprintln!("Usage: selfplay --output FILE [--games N] [--colors 2-8] [--size N] [--pieces MAX_SIZE]");
+    eprintln!("                [--iterations N] [--weights FILE] [--temperature T] [--temperature-moves N]");
+    eprintln!("                [--noise-alpha A] [--noise-fraction F] [--resign VALUE] [--seed S]");
+    eprintln!("       selfplay --read FILE");
+    eprintln!();
+    eprintln!("Plays games of evaluator-guided tree search against itself and records every");
+    eprintln!("position with the visits of the search; see `blokus::selfplay` for the file format.");
+    eprintln!("Without --weights, every position is evaluated as even. --read summarizes a file.");
+    process::exit(2);
+}
+
+fn parse_args() -> Config {
+    let mut config = Config {
+        games: 10,
+        header: Header { size: 20, colors: 4, piece_size: 5 },
+        selfplay: SelfPlayConfig::default(),
+        weights: None,
+        seed: None,
+        output: None,
+        read: None,
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().unwrap_or_else(|| usage());
+        let number = || value.parse::<usize>().unwrap_or_else(|_| usage());
+        let real = || value.parse::<f64>().unwrap_or_else(|_| usage());
+        match arg.as_str() {
+            "--games" => config.games = number(),
+            "--colors" => config.header.colors = number(),
+            "--size" => config.header.size = number(),
+            "--pieces" => config.header.piece_size = number(),
+            "--iterations" => config.selfplay.iterations = number(),
+            "--temperature" => config.selfplay.temperature = real(),
+            "--temperature-moves" => config.selfplay.temperature_moves = number(),
+            "--noise-alpha" => config.selfplay.noise_alpha = real(),
+            "--noise-fraction" => config.selfplay.noise_fraction = real(),
+            "--resign" => config.selfplay.resign_threshold = Some(real()),
+            "--weights" => config.weights = Some(value),
+            "--seed" => config.seed = Some(number() as u64),
+            "--output" => config.output = Some(value),
+            "--read" => config.read = Some(value),
+            _ => usage(),
+        }
+    }
+    if let Err(e) = config.header.new_game() {
+        eprintln!("{}", e);
+        usage();
+    }
+    if config.selfplay.noise_alpha <= 0.0 || config.output.is_some() == config.read.is_some() {
+        usage();
+    }
+    config
+}
+
+fn fail<E: std::fmt::Display>(path: &str, e: E) -> ! {
+    eprintln!("{}: {}", path, e);
+    process::exit(1);
+}
+
+fn summarize(path: &str) {
+    let file = File::open(path).unwrap_or_else(|e| fail(path, e));
+    let mut reader = Reader::new(BufReader::new(file)).unwrap_or_else(|e| fail(path, e));
+    let header = *reader.header();
+    println!("Board {}, {} colors, pieces up to size {}", header.size, header.colors, header.piece_size);
+    let mut games = 0;
+    let mut positions = 0;
+    while let Some(record) = reader.next_game().unwrap_or_else(|e| fail(path, e)) {
+        games += 1;
+        positions += record.positions.len();
+        if let Err(e) = record.examples(&header, false) {
+            fail(path, format!("game {}: {}", games, e));
+        }
+        let resigned = record.resigned.map_or(String::new(), |c| format!(", color {} resigned", c));
+        println!("Game {}: {} positions, scores {:?}{}", games, record.positions.len(), record.scores, resigned);
+    }
+    println!("{} games, {} positions", games, positions);
+}
+
+fn main() {
+    let config = parse_args();
+    if let Some(ref path) = config.read {
+        summarize(path);
+        return;
+    }
+    let mut evaluator: Box<dyn Evaluator> = match config.weights {
+        Some(ref path) => {
+            let net = ConvNet::load(path).unwrap_or_else(|e| fail(path, e));
+            net.check(config.header.new_game().unwrap().board()).unwrap_or_else(|e| fail(path, e));
+            Box::new(net)
+        },
+        None => Box::new(UniformEvaluator),
+    };
+    let seed = config.seed.unwrap_or_else(|| thread_rng().gen());
+    let mut rng = StdRng::seed_from_u64(seed);
+    let path = config.output.as_ref().unwrap();
+    let mut output = BufWriter::new(File::create(path).unwrap_or_else(|e| fail(path, e)));
+    write_header(&mut output, &config.header).unwrap_or_else(|e| fail(path, e));
+
+    println!("Seed {}", seed);
+    for g in 0..config.games {
+        let mut game = config.header.new_game().unwrap();
+        let record = play_game(&mut game, &mut evaluator, &config.selfplay, &mut rng);
+        write_game(&mut output, &record).unwrap_or_else(|e| fail(path, e));
+        let resigned = record.resigned.map_or(String::new(), |c| format!(", color {} resigned", c));
+        println!("Game {}: {} positions, scores {:?}{}", g + 1, record.positions.len(), record.scores, resigned);
+    }
+    output.flush().unwrap_or_else(|e| fail(path, e));
+}
@@ -0,0 +1,380 @@
+//! The games hosted by the server and the JSON API to them.
+//! Every request is answered with JSON.
+//! Moves are written in the notation of `notation`.
+//!
+//! Endpoints:
+//!
+//! ```text
+//! GET    /games                  ids of the hosted games
+//! POST   /games?size=&colors=&pieces=
+//!                                new game; defaults are 20, 4 and 5
+//! GET    /games/ID               board, scores, color to move and moves played
+//! DELETE /games/ID               forget the game
+//! GET    /games/ID/legal         legal moves of the color to move
+//! POST   /games/ID/moves         play the move given as the request body
+//! POST   /games/ID/ai?agent=     let an agent (default `greedy`) play a move; one of
+//!                                random, greedy, mcts:N, puct:N, paranoid:MS or maxn:MS
+//! POST   /games/ID/undo          take back the last move
+//! GET    /games/ID/events        WebSocket stream of `state`, `move` and `undo` events
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::mpsc::Sender;
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use blokus::agent::{Agent, parse_agent};
+use blokus::board::Move;
+use blokus::game::Game;
+use blokus::notation::{format_move, parse_move};
+use blokus::protocol::board_to_text;
+use blokus::treesearch::GameStateMut;
+
+/// Most iterations an agent may be given through the API.
+const MAX_ITERATIONS: u64 = 20_000;
+/// Most milliseconds an agent may be given through the API.
+const MAX_MILLIS: u64 = 5_000;
+
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Response {
+    fn json(status: u16, body: String) -> Self {
+        Response { status, body }
+    }
+
+    pub fn error(status: u16, message: &str) -> Self {
+        Response::json(status, format!("{{\"error\":{}}}", json_string(message)))
+    }
+}
+
+/// A message for the thread writing to a WebSocket.
+pub enum Frame {
+    Text(String),
+    Pong(Vec<u8>),
+    Close,
+}
+
+struct Hosted {
+    game: Game,
+    listeners: Vec<Sender<Frame>>,
+}
+
+impl Hosted {
+    fn broadcast(&mut self, event: &str) {
+        self.listeners.retain(|l| l.send(Frame::Text(event.to_string())).is_ok());
+    }
+}
+
+struct Games {
+    next_id: usize,
+    games: HashMap<usize, Hosted>,
+    rng: StdRng,
+}
+
+pub struct Server {
+    games: Mutex<Games>,
+}
+
+fn json_string(s: &str) -> String {
+    let mut res = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            c if (c as u32) < 0x20 => res.push_str(&format!("\\u{:04x}", c as u32)),
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+fn json_list<I: IntoIterator<Item = String>>(items: I) -> String {
+    format!("[{}]", items.into_iter().collect::<Vec<_>>().join(","))
+}
+
+fn state_json(id: usize, game: &mut Game) -> String {
+    let to_move = if game.move_count() == 0 { "null".to_string() } else { game.to_move().to_string() };
+    let board = game.board();
+    format!("{{\"id\":{},\"rows\":{},\"cols\":{},\"colors\":{},\"to_move\":{},\"scores\":{},\"board\":{},\"moves\":{}}}",
+            id, board.rows(), board.cols(), board.colors(), to_move,
+            json_list(game.scores().iter().map(|s| s.to_string())),
+            json_list(board_to_text(board).lines().map(json_string)),
+            json_list(game.history().iter().map(|&mv| json_string(&format_move(board, mv)))))
+}
+
+fn move_event(id: usize, game: &mut Game, mv: Option<Move>) -> String {
+    let color = (game.history().len() - 1) % game.board().colors();
+    let text = format_move(game.board(), mv);
+    format!("{{\"event\":\"move\",\"color\":{},\"move\":{},\"state\":{}}}",
+            color, json_string(&text), state_json(id, game))
+}
+
+fn percent_decode(s: &str) -> String {
+    let hex = |b: u8| (b as char).to_digit(16).map(|d| d as u8);
+    let bytes = s.as_bytes();
+    let mut res = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => res.push(b' '),
+            b'%' if i + 2 < bytes.len() && hex(bytes[i + 1]).is_some() && hex(bytes[i + 2]).is_some() => {
+                res.push(hex(bytes[i + 1]).unwrap() * 16 + hex(bytes[i + 2]).unwrap());
+                i += 2;
+            },
+            b => res.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&res).into_owned()
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query.split('&').filter(|p| !p.is_empty()).map(|p| {
+        let mut kv = p.splitn(2, '=');
+        (percent_decode(kv.next().unwrap()), percent_decode(kv.next().unwrap_or("")))
+    }).collect()
+}
+
+/// The agent asked for by a client. Any client may ask, so only agents that
+/// need no files are offered, and their effort is capped.
+fn client_agent(spec: &str) -> Result<Box<dyn Agent>, String> {
+    let mut parts = spec.splitn(2, ':');
+    let name = parts.next().unwrap();
+    let max = match name {
+        "random" | "greedy" => 0,
+        "mcts" | "puct" => MAX_ITERATIONS,
+        "paranoid" | "maxn" => MAX_MILLIS,
+        _ => return Err(format!("unknown agent {:?}", spec)),
+    };
+    if let Some(arg) = parts.next() {
+        match arg.parse::<u64>() {
+            Ok(n) if n <= max => (),
+            _ => return Err(format!("agent {} takes a number up to {}", name, max)),
+        }
+    }
+    parse_agent(spec)
+}
+
+fn new_game(query: &HashMap<String, String>) -> Result<Game, String> {
+    let number = |key: &str, default: usize| match query.get(key) {
+        None => Ok(default),
+        Some(v) => v.parse::<usize>().map_err(|_| format!("{} must be a number", key)),
+    };
+    let size = number("size", 20)?;
+    let colors = number("colors", 4)?;
+    let piece_size = number("pieces", 5)?;
+    Game::standard(size, colors, piece_size).map_err(|e| e.to_string())
+}
+
+impl Server {
+    pub fn new(rng: StdRng) -> Self {
+        Server {
+            games: Mutex::new(Games { next_id: 1, games: HashMap::new(), rng }),
+        }
+    }
+
+    /// Run `f` on the game with the given id, or answer 404.
+    fn with_game<F>(&self, id: &str, f: F) -> Response
+        where F: FnOnce(usize, &mut Hosted) -> Response
+    {
+        let mut games = self.games.lock().unwrap();
+        match id.parse::<usize>().ok().and_then(|id| games.games.get_mut(&id).map(|g| (id, g))) {
+            Some((id, hosted)) => f(id, hosted),
+            None => Response::error(404, "no such game"),
+        }
+    }
+
+    /// Answer a request other than a WebSocket upgrade.
+    pub fn handle(&self, method: &str, target: &str, body: &str) -> Response {
+        let mut split = target.splitn(2, '?');
+        let path = split.next().unwrap();
+        let query = parse_query(split.next().unwrap_or(""));
+        let segments = path.split('/').filter(|s| !s.is_empty()).collect::<Vec<_>>();
+        match (method, segments.as_slice()) {
+            ("OPTIONS", _) => Response::json(204, String::new()),
+            ("GET", ["games"]) => {
+                let games = self.games.lock().unwrap();
+                let mut ids = games.games.keys().cloned().collect::<Vec<_>>();
+                ids.sort_unstable();
+                Response::json(200, json_list(ids.iter().map(|id| id.to_string())))
+            },
+            ("POST", ["games"]) => match new_game(&query) {
+                Ok(mut game) => {
+                    let mut games = self.games.lock().unwrap();
+                    let id = games.next_id;
+                    games.next_id += 1;
+                    let body = state_json(id, &mut game);
+                    games.games.insert(id, Hosted { game, listeners: Vec::new() });
+                    Response::json(201, body)
+                },
+                Err(e) => Response::error(400, &e),
+            },
+            ("GET", ["games", id]) => self.with_game(id, |id, hosted| Response::json(200, state_json(id, &mut hosted.game))),
+            ("DELETE", ["games", id]) => {
+                let mut games = self.games.lock().unwrap();
+                match id.parse::<usize>().ok().and_then(|id| games.games.remove(&id)) {
+                    Some(hosted) => {
+                        for l in hosted.listeners {
+                            let _ = l.send(Frame::Close);
+                        }
+                        Response::json(204, String::new())
+                    },
+                    None => Response::error(404, "no such game"),
+                }
+            },
+            ("GET", ["games", id, "legal"]) => self.with_game(id, |_, hosted| {
+                let game = &mut hosted.game;
+                let moves = if game.move_count() == 0 {
+                    Vec::new()
+                } else if game.must_pass() {
+                    vec![None]
+                } else {
+                    game.legal_moves().iter().map(|&m| Some(m)).collect()
+                };
+                let moves = json_list(moves.into_iter().map(|m| json_string(&format_move(game.board(), m))));
+                Response::json(200, format!("{{\"color\":{},\"moves\":{}}}", game.to_move(), moves))
+            }),
+            ("POST", ["games", id, "moves"]) => self.with_game(id, |id, hosted| {
+                if hosted.game.move_count() == 0 {
+                    return Response::error(409, "the game is over");
+                }
+                let color = hosted.game.to_move();
+                let mv = match parse_move(hosted.game.board(), color, body) {
+                    Ok(mv) => mv,
+                    Err(e) => return Response::error(400, &e),
+                };
+                if let Err(e) = hosted.game.play(mv) {
+                    return Response::error(400, &e.to_string());
+                }
+                let event = move_event(id, &mut hosted.game, mv);
+                hosted.broadcast(&event);
+                Response::json(200, event)
+            }),
+            ("POST", ["games", id, "ai"]) => self.ai_move(id, query.get("agent").map_or("greedy", |a| a.as_str())),
+            ("POST", ["games", id, "undo"]) => self.with_game(id, |id, hosted| {
+                if hosted.game.take_back().is_none() {
+                    return Response::error(409, "nothing to undo");
+                }
+                let event = format!("{{\"event\":\"undo\",\"state\":{}}}", state_json(id, &mut hosted.game));
+                hosted.broadcast(&event);
+                Response::json(200, event)
+            }),
+            _ => Response::error(404, "no such endpoint"),
+        }
+    }
+
+    /// Let an agent choose a move on a copy of the game, so that other
+    /// requests are not blocked while it thinks.
+    fn ai_move(&self, id: &str, spec: &str) -> Response {
+        let mut agent = match client_agent(spec) {
+            Ok(agent) => agent,
+            Err(e) => return Response::error(400, &e),
+        };
+        let (mut game, seed) = {
+            let mut games = self.games.lock().unwrap();
+            let seed = games.rng.gen::<u64>();
+            match id.parse::<usize>().ok().and_then(|id| games.games.get(&id)) {
+                Some(hosted) => (hosted.game.clone(), seed),
+                None => return Response::error(404, "no such game"),
+            }
+        };
+        if game.move_count() == 0 {
+            return Response::error(409, "the game is over");
+        }
+        if let Err(e) = agent.check(game.board()) {
+            return Response::error(400, &e);
+        }
+        let mv = agent.choose(&mut game, &mut StdRng::seed_from_u64(seed));
+        self.play_searched_move(id, game.history(), mv)
+    }
+
+    /// Play `mv`, found for the position after `searched`, unless the game
+    /// has moved on or been taken back since. Comparing the whole history
+    /// catches an undo followed by another move, which keeps its length.
+    fn play_searched_move(&self, id: &str, searched: &[Option<Move>], mv: Option<Move>) -> Response {
+        self.with_game(id, |id, hosted| {
+            if hosted.game.history() != searched {
+                return Response::error(409, "the game changed while the agent was thinking");
+            }
+            if let Err(e) = hosted.game.play(mv) {
+                return Response::error(409, &e.to_string());
+            }
+            let event = move_event(id, &mut hosted.game, mv);
+            hosted.broadcast(&event);
+            Response::json(200, event)
+        })
+    }
+
+    /// Register a WebSocket writer for the events of a game
+    /// and send it the current state.
+    pub fn subscribe(&self, id: &str, listener: Sender<Frame>) -> bool {
+        let mut games = self.games.lock().unwrap();
+        match id.parse::<usize>().ok().and_then(|id| games.games.get_mut(&id).map(|g| (id, g))) {
+            Some((id, hosted)) => {
+                let event = format!("{{\"event\":\"state\",\"state\":{}}}", state_json(id, &mut hosted.game));
+                let _ = listener.send(Frame::Text(event));
+                hosted.listeners.push(listener);
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+#[test]
+fn test_percent_decode() {
+    assert_eq!(percent_decode("mcts%3A10+x"), "mcts:10 x");
+}
+
+#[test]
+fn test_rest_api() {
+    let server = Server::new(StdRng::seed_from_u64(0));
+    let created = server.handle("POST", "/games?size=5&colors=2&pieces=2", "");
+    assert_eq!(created.status, 201);
+    assert!(created.body.starts_with("{\"id\":1,\"rows\":5,\"cols\":5,\"colors\":2,\"to_move\":0,"), "{}", created.body);
+    assert_eq!(server.handle("GET", "/games", "").body, "[1]");
+    assert_eq!(server.handle("POST", "/games?colors=9", "").status, 400);
+
+    let legal = server.handle("GET", "/games/1/legal", "");
+    assert!(legal.body.contains("\"a1,b1\""), "{}", legal.body);
+    let played = server.handle("POST", "/games/1/moves", "a1,b1");
+    assert_eq!(played.status, 200);
+    assert!(played.body.starts_with("{\"event\":\"move\",\"color\":0,\"move\":\"a1,b1\""), "{}", played.body);
+    assert_eq!(server.handle("POST", "/games/1/moves", "a2").status, 400);
+    assert_eq!(server.handle("POST", "/games/1/ai?agent=nobody", "").status, 400);
+    // Clients cannot name files or ask for unbounded effort.
+    assert_eq!(server.handle("POST", "/games/1/ai?agent=puct%3A10%3A%2Fetc%2Fpasswd", "").status, 400);
+    assert_eq!(server.handle("POST", "/games/1/ai?agent=mcts%3A1000000000", "").status, 400);
+    assert_eq!(server.handle("POST", "/games/1/ai?agent=paranoid%3A86400000", "").status, 400);
+    let ai = server.handle("POST", "/games/1/ai?agent=mcts%3A10", "");
+    assert!(ai.body.contains("\"color\":1"), "{}", ai.body);
+    let state = server.handle("GET", "/games/1", "");
+    assert!(state.body.contains("\"board\":[\"00...\","), "{}", state.body);
+    assert!(state.body.contains("\"to_move\":0"), "{}", state.body);
+
+    // A move found for a position that was taken back and replaced by another
+    // of the same length is not played.
+    let searched = server.games.lock().unwrap().games[&1].game.history().to_vec();
+    assert_eq!(server.handle("POST", "/games/1/undo", "").status, 200);
+    {
+        let mut games = server.games.lock().unwrap();
+        let game = &mut games.games.get_mut(&1).unwrap().game;
+        let other = game.legal_moves().iter().cloned().find(|&mv| Some(mv) != searched[1]).unwrap();
+        game.play(Some(other)).unwrap();
+    }
+    assert_eq!(server.play_searched_move("1", &searched, None).status, 409);
+
+    assert_eq!(server.handle("POST", "/games/1/undo", "").status, 200);
+    assert_eq!(server.handle("POST", "/games/1/undo", "").status, 200);
+    assert_eq!(server.handle("POST", "/games/1/undo", "").status, 409);
+    assert_eq!(server.handle("DELETE", "/games/1", "").status, 204);
+    assert_eq!(server.handle("GET", "/games/1", "").status, 404);
+    assert_eq!(server.handle("GET", "/nowhere", "").status, 404);
+}
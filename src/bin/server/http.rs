@@ -0,0 +1,321 @@
+//! HTTP/1.1 and WebSocket connections to the server, with only the standard library.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::Duration;
+
+use api::{Frame, Response, Server};
+
+/// Largest request body or WebSocket message we accept.
+const MAX_BODY: usize = 1 << 16;
+
+/// How long a client may take to send a request.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        101 => "Switching Protocols",
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        _ => "",
+    }
+}
+
+fn write_response<W: Write>(w: &mut W, response: &Response) -> io::Result<()> {
+    write!(w, "HTTP/1.1 {} {}\r\n", response.status, reason(response.status))?;
+    write!(w, "Content-Type: application/json\r\nContent-Length: {}\r\n", response.body.len())?;
+    write!(w, "Access-Control-Allow-Origin: *\r\n")?;
+    write!(w, "Access-Control-Allow-Methods: GET, POST, DELETE, OPTIONS\r\n")?;
+    write!(w, "Access-Control-Allow-Headers: Content-Type\r\n")?;
+    write!(w, "Connection: close\r\n\r\n{}", response.body)?;
+    w.flush()
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x6745_2301, 0xEFCD_AB89, 0x98BA_DCFE, 0x1032_5476, 0xC3D2_E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+    for chunk in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for i in 0..16 {
+            w[i] = u32::from_be_bytes([chunk[4 * i], chunk[4 * i + 1], chunk[4 * i + 2], chunk[4 * i + 3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let (mut a, mut b, mut c, mut d, mut e) = (h[0], h[1], h[2], h[3], h[4]);
+        for (i, &wi) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ED9_EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1B_BCDC),
+                _ => (b ^ c ^ d, 0xCA62_C1D6),
+            };
+            let t = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(wi);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+        for (x, y) in h.iter_mut().zip(&[a, b, c, d, e]) {
+            *x = x.wrapping_add(*y);
+        }
+    }
+    let mut res = [0; 20];
+    for i in 0..5 {
+        res[4 * i..4 * i + 4].copy_from_slice(&h[i].to_be_bytes());
+    }
+    res
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut res = String::new();
+    for chunk in data.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                res.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                res.push('=');
+            }
+        }
+    }
+    res
+}
+
+/// The `Sec-WebSocket-Accept` answer to a `Sec-WebSocket-Key`.
+fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, WEBSOCKET_GUID).as_bytes()))
+}
+
+fn write_frame<W: Write>(w: &mut W, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut header = vec![0x80 | opcode];
+    if payload.len() < 126 {
+        header.push(payload.len() as u8);
+    } else if payload.len() < 1 << 16 {
+        header.push(126);
+        header.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+    } else {
+        header.push(127);
+        header.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+    }
+    w.write_all(&header)?;
+    w.write_all(payload)?;
+    w.flush()
+}
+
+/// Read one frame and return its opcode and unmasked payload.
+/// Frames from a client must be masked and frames from a server must not.
+fn read_frame<R: Read>(r: &mut R, from_client: bool) -> io::Result<(u8, Vec<u8>)> {
+    let mut head = [0; 2];
+    r.read_exact(&mut head)?;
+    let mut len = (head[1] & 0x7f) as u64;
+    if len == 126 {
+        let mut ext = [0; 2];
+        r.read_exact(&mut ext)?;
+        len = u16::from_be_bytes(ext) as u64;
+    } else if len == 127 {
+        let mut ext = [0; 8];
+        r.read_exact(&mut ext)?;
+        len = u64::from_be_bytes(ext);
+    }
+    if len > MAX_BODY as u64 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "frame too large"));
+    }
+    let masked = head[1] & 0x80 != 0;
+    if masked != from_client {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "frame masked the wrong way"));
+    }
+    let mut mask = [0; 4];
+    if masked {
+        r.read_exact(&mut mask)?;
+    }
+    let mut payload = vec![0; len as usize];
+    r.read_exact(&mut payload)?;
+    for (i, b) in payload.iter_mut().enumerate() {
+        *b ^= mask[i % 4];
+    }
+    Ok((head[0] & 0x0f, payload))
+}
+
+/// Stream the events of a game until either side closes the connection.
+fn websocket<R: Read + Send + 'static>(server: &Server, id: &str, key: &str, mut reader: R, mut stream: TcpStream) -> io::Result<()> {
+    let (sender, frames) = channel();
+    if !server.subscribe(id, sender.clone()) {
+        return write_response(&mut stream, &Response::error(404, "no such game"));
+    }
+    write!(stream, "HTTP/1.1 101 {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n", reason(101))?;
+    write!(stream, "Sec-WebSocket-Accept: {}\r\n\r\n", accept_key(key))?;
+    stream.flush()?;
+    // Event streams may stay quiet for as long as the game does.
+    stream.set_read_timeout(None)?;
+    thread::spawn(move || {
+        loop {
+            match read_frame(&mut reader, true) {
+                Ok((0x9, payload)) => {
+                    let _ = sender.send(Frame::Pong(payload));
+                },
+                Ok((0x8, _)) | Err(_) => break,
+                Ok(_) => (),
+            }
+        }
+        let _ = sender.send(Frame::Close);
+    });
+    for frame in frames {
+        match frame {
+            Frame::Text(text) => write_frame(&mut stream, 0x1, text.as_bytes())?,
+            Frame::Pong(payload) => write_frame(&mut stream, 0xA, &payload)?,
+            Frame::Close => {
+                let _ = write_frame(&mut stream, 0x8, &[]);
+                break;
+            },
+        }
+    }
+    let _ = stream.shutdown(Shutdown::Both);
+    Ok(())
+}
+
+fn handle_connection(server: &Server, mut stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method.to_string(), target.to_string()),
+        _ => return Ok(()),
+    };
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+    let length = headers.get("content-length").and_then(|l| l.parse::<usize>().ok()).unwrap_or(0);
+    if length > MAX_BODY {
+        return write_response(&mut stream, &Response::error(413, "request body too large"));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    let upgrade = headers.get("upgrade").map_or(false, |u| u.eq_ignore_ascii_case("websocket"));
+    if method == "GET" && upgrade {
+        let path = target.split('?').next().unwrap();
+        let segments = path.split('/').filter(|s| !s.is_empty()).collect::<Vec<_>>();
+        if headers.get("sec-websocket-version").map(|v| v.as_str()) != Some("13") {
+            return write_response(&mut stream, &Response::error(400, "expected Sec-WebSocket-Version 13"));
+        }
+        return match (segments.as_slice(), headers.get("sec-websocket-key")) {
+            (["games", id, "events"], Some(key)) => websocket(server, id, key, reader, stream),
+            _ => write_response(&mut stream, &Response::error(400, "expected /games/ID/events and a Sec-WebSocket-Key")),
+        };
+    }
+    let response = server.handle(&method, &target, &String::from_utf8_lossy(&body));
+    write_response(&mut stream, &response)
+}
+
+/// Answer connections on `listener`, one thread per connection.
+pub fn serve(server: Arc<Server>, listener: TcpListener) -> io::Result<()> {
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let server = server.clone();
+        thread::spawn(move || {
+            let _ = handle_connection(&server, stream);
+        });
+    }
+    Ok(())
+}
+
+#[test]
+fn test_accept_key() {
+    // The example from RFC 6455.
+    assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    assert_eq!(base64(b"ab"), "YWI=");
+    assert_eq!(base64(b"a"), "YQ==");
+}
+
+#[test]
+fn test_http_and_websocket() {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    let server = Arc::new(Server::new(StdRng::seed_from_u64(0)));
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let s = server.clone();
+    thread::spawn(move || serve(s, listener));
+
+    let mut http = TcpStream::connect(addr).unwrap();
+    http.write_all(b"POST /games?size=6&colors=2&pieces=3 HTTP/1.1\r\nContent-Length: 0\r\n\r\n").unwrap();
+    let mut response = String::new();
+    http.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 201 Created\r\n"), "{}", response);
+    assert!(response.ends_with("\"moves\":[]}"), "{}", response);
+
+    let mut ws = TcpStream::connect(addr).unwrap();
+    ws.write_all(b"GET /games/1/events HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                   Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n").unwrap();
+    let mut reader = BufReader::new(ws.try_clone().unwrap());
+    let mut headers = String::new();
+    while !headers.ends_with("\r\n\r\n") {
+        reader.read_line(&mut headers).unwrap();
+    }
+    assert!(headers.starts_with("HTTP/1.1 101"), "{}", headers);
+    assert!(headers.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"), "{}", headers);
+    let (opcode, payload) = read_frame(&mut reader, false).unwrap();
+    assert_eq!(opcode, 0x1);
+    assert!(String::from_utf8(payload).unwrap().starts_with("{\"event\":\"state\""));
+
+    let mut http = TcpStream::connect(addr).unwrap();
+    http.write_all(b"POST /games/1/moves HTTP/1.1\r\nContent-Length: 8\r\n\r\na1,a2,a3").unwrap();
+    let mut response = String::new();
+    http.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    let (_, payload) = read_frame(&mut reader, false).unwrap();
+    assert!(String::from_utf8(payload).unwrap().starts_with("{\"event\":\"move\",\"color\":0,\"move\":\"a1,a2,a3\""));
+
+    // A masked close frame from the client is answered with a close frame.
+    ws.write_all(&[0x88, 0x80, 1, 2, 3, 4]).unwrap();
+    assert_eq!(read_frame(&mut reader, false).unwrap().0, 0x8);
+
+    // An unmasked frame from the client closes the stream too.
+    let mut ws = TcpStream::connect(addr).unwrap();
+    ws.write_all(b"GET /games/1/events HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                   Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n").unwrap();
+    let mut reader = BufReader::new(ws.try_clone().unwrap());
+    let mut headers = String::new();
+    while !headers.ends_with("\r\n\r\n") {
+        reader.read_line(&mut headers).unwrap();
+    }
+    assert_eq!(read_frame(&mut reader, false).unwrap().0, 0x1);
+    ws.write_all(&[0x89, 0x00]).unwrap();
+    assert_eq!(read_frame(&mut reader, false).unwrap().0, 0x8);
+
+    // Other WebSocket versions are turned away.
+    let mut ws = TcpStream::connect(addr).unwrap();
+    ws.write_all(b"GET /games/1/events HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                   Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 8\r\n\r\n").unwrap();
+    let mut response = String::new();
+    ws.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{}", response);
+}
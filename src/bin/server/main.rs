@@ -0,0 +1,47 @@
+extern crate blokus;
+extern crate rand;
+
+use std::env;
+use std::net::TcpListener;
+use std::process;
+use std::sync::Arc;
+
+use rand::{SeedableRng, thread_rng, Rng};
+use rand::rngs::StdRng;
+
+mod api;
+mod http;
+
+use api::Server;
+use http::serve;
+
+fn usage() -> ! {
+    eprintln!("Usage: server [--port N] [--seed S]");
+    eprintln!();
+    eprintln!("Hosts games over HTTP on 127.0.0.1; the endpoints are listed in src/bin/server/api.rs.");
+    process::exit(2);
+}
+
+fn main() {
+    let mut port = 8080;
+    let mut seed = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--port" => port = value.parse::<u16>().unwrap_or_else(|_| usage()),
+            "--seed" => seed = Some(value.parse::<u64>().unwrap_or_else(|_| usage())),
+            _ => usage(),
+        }
+    }
+    let seed = seed.unwrap_or_else(|| thread_rng().gen());
+    let listener = TcpListener::bind(("127.0.0.1", port)).unwrap_or_else(|e| {
+        eprintln!("Could not listen on port {}: {}", port, e);
+        process::exit(1);
+    });
+    println!("Listening on http://127.0.0.1:{}", port);
+    if let Err(e) = serve(Arc::new(Server::new(StdRng::seed_from_u64(seed))), listener) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,154 @@
+extern crate blokus;
+extern crate rand;
+
+use std::env;
+use std::fs::File;
+use std::io::Write;
+use std::process;
+
+use rand::{SeedableRng, thread_rng, Rng};
+use rand::rngs::StdRng;
+
+use blokus::agent::{Agent, parse_agent};
+use blokus::game::Game;
+use blokus::notation::format_record;
+use blokus::rating::Results;
+use blokus::treesearch::GameStateMut;
+
+struct Config {
+    games: usize,
+    agents: Vec<String>,
+    colors: usize,
+    size: usize,
+    piece_size: usize,
+    seed: Option<u64>,
+    records: Option<String>,
+}
+
+fn usage() -> ! {
+    eprintln!("Usage: tournament [--games N] [--agents AGENT,AGENT,...] [--colors 2-8]");
+    eprintln!("                  [--size N] [--pieces MAX_SIZE] [--seed S] [--records FILE]");
+    eprintln!();
+    eprintln!("AGENT is random, greedy, mcts:ITERATIONS, paranoid:MS, maxn:MS or puct:ITERATIONS[:WEIGHTS].");
+    eprintln!("Seats are rotated between games so every agent plays every color.");
+    process::exit(2);
+}
+
+fn parse_args() -> Config {
+    let mut config = Config {
+        games: 12,
+        agents: vec!["greedy".to_string(), "random".to_string()],
+        colors: 4,
+        size: 20,
+        piece_size: 5,
+        seed: None,
+        records: None,
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().unwrap_or_else(|| usage());
+        let number = || value.parse::<usize>().unwrap_or_else(|_| usage());
+        match arg.as_str() {
+            "--games" => config.games = number(),
+            "--colors" => config.colors = number(),
+            "--size" => config.size = number(),
+            "--pieces" => config.piece_size = number(),
+            "--seed" => config.seed = Some(number() as u64),
+            "--agents" => config.agents = value.split(',').map(|s| s.to_string()).collect(),
+            "--records" => config.records = Some(value),
+            _ => usage(),
+        }
+    }
+    if let Err(e) = Game::standard(config.size, config.colors, config.piece_size) {
+        eprintln!("{}", e);
+        usage();
+    }
+    config
+}
+
+fn new_game(config: &Config) -> Game {
+    Game::standard(config.size, config.colors, config.piece_size).unwrap()
+}
+
+/// Play one game where `players[c]` is the agent playing color `c`,
+/// and return the final scores by color.
+fn play(game: &mut Game, agents: &mut [Box<dyn Agent>], players: &[usize], rng: &mut StdRng) -> Vec<i32> {
+    while game.move_count() > 0 {
+        let p = players[game.to_move() as usize];
+        let mv = agents[p].choose(game, rng);
+        game.play(mv).unwrap_or_else(|e| {
+            eprintln!("{} played an illegal move: {}", agents[p].name(), e);
+            process::exit(1);
+        });
+    }
+    game.scores()
+}
+
+fn main() {
+    let config = parse_args();
+    let mut agents = config.agents.iter().map(|spec| parse_agent(spec).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        usage()
+    })).collect::<Vec<_>>();
+    let board = new_game(&config).board().clone();
+    for agent in agents.iter() {
+        agent.check(&board).unwrap_or_else(|e| {
+            eprintln!("{}: {}", agent.name(), e);
+            process::exit(1);
+        });
+    }
+    let seed = config.seed.unwrap_or_else(|| thread_rng().gen());
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut records = config.records.as_ref().map(|path| File::create(path).unwrap_or_else(|e| {
+        eprintln!("{}: {}", path, e);
+        process::exit(1);
+    }));
+
+    let n = agents.len();
+    let mut results = Results::new(n);
+    let mut seats_played = vec![0; n];
+    let mut wins = vec![0.0; n];
+    let mut score_sums = vec![0; n];
+    println!("Seed {}", seed);
+    for g in 0..config.games {
+        let players = (0..config.colors).map(|c| (c + g) % n).collect::<Vec<_>>();
+        let mut game = new_game(&config);
+        let scores = play(&mut game, &mut agents, &players, &mut rng);
+        let best = *scores.iter().max().unwrap();
+        let winners = scores.iter().filter(|&&s| s == best).count() as f64;
+        for (seat, &p) in players.iter().enumerate() {
+            seats_played[p] += 1;
+            score_sums[p] += scores[seat];
+            if scores[seat] == best {
+                wins[p] += 1.0 / winners;
+            }
+        }
+        results.add_game(&players, &scores);
+        let line = players.iter().zip(scores.iter())
+            .map(|(&p, s)| format!("{} {}", agents[p].name(), s))
+            .collect::<Vec<_>>().join(", ");
+        println!("Game {}: {}", g + 1, line);
+        if let Some(ref mut f) = records {
+            let mut tags = vec![("Game".to_string(), (g + 1).to_string())];
+            for (seat, &p) in players.iter().enumerate() {
+                tags.push((format!("Seat{}", seat), agents[p].name()));
+            }
+            tags.push(("Scores".to_string(), scores.iter().map(|s| s.to_string()).collect::<Vec<_>>().join(" ")));
+            let record = format_record(game.board(), &tags, game.history());
+            writeln!(f, "{}", record).unwrap_or_else(|e| {
+                eprintln!("Could not write record: {}", e);
+                process::exit(1);
+            });
+        }
+    }
+
+    let ratings = results.ratings();
+    println!();
+    println!("{:<16} {:>6} {:>7} {:>6} {:>10} {:>7} {:>6}", "agent", "seats", "wins", "win%", "avg score", "elo", "±95%");
+    for p in 0..n {
+        let seats = seats_played[p].max(1) as f64;
+        println!("{:<16} {:>6} {:>7.1} {:>5.1}% {:>10.2} {:>7.0} {:>6.0}",
+                 agents[p].name(), seats_played[p], wins[p], 100.0 * wins[p] / seats,
+                 score_sums[p] as f64 / seats, ratings[p].elo, ratings[p].margin);
+    }
+}
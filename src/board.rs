@@ -1,32 +1,71 @@
+use std::collections::HashSet;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use error::BlokusError;
 use pieces::{Piece, Point};
-use neighbors::{DiagonalIterator, CardinalIterator};
+use neighbors::{DiagonalIterator, CardinalIterator, Topology};
+use symmetry::{Transform, TRANSFORMS};
 
 pub type Color = u8;
 
+/// `(piece, variation, offset)` as listed in `Moves::moves`.
+pub type Move = (usize, usize, usize);
+
+/// The cells of a piece with the top left corner of its bounding box at `(x, y)`;
+/// points beyond the right or bottom edge continue on the other side.
+#[derive(Clone)]
 struct Translation<'a> {
-    indices: &'a Vec<usize>,
-    offset: usize,
+    variation: &'a BoardPieceVariation,
+    x: usize,
+    y: usize,
+    rows: usize,
+    cols: usize,
+    /// Whether the piece crosses an edge, so that its cells are not simply
+    /// the indices of its points added to the anchor.
+    wraps: bool,
+    anchor: usize,
     i: usize,
 }
 
+impl<'a> Translation<'a> {
+    fn new(variation: &'a BoardPieceVariation, x: usize, y: usize, rows: usize, cols: usize) -> Self {
+        let wraps = x + variation.width > cols || y + variation.height > rows;
+        Translation { variation, x, y, rows, cols, wraps, anchor: x + y * cols, i: 0 }
+    }
+}
+
 impl<'a> Iterator for Translation<'a> {
     type Item = usize;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.i == self.indices.len() {
+        if self.i == self.variation.indices.len() {
             return None;
         }
-        let res = Some(self.indices[self.i] + self.offset);
+        let i = self.i;
         self.i += 1;
-        res
+        if !self.wraps {
+            return Some(self.variation.indices[i] + self.anchor);
+        }
+        let (px, py) = self.variation.points[i];
+        let mut x = self.x + px;
+        if x >= self.cols {
+            x -= self.cols;
+        }
+        let mut y = self.y + py;
+        if y >= self.rows {
+            y -= self.rows;
+        }
+        Some(x + y * self.cols)
     }
 }
 
 struct TranslationsIterator<'a> {
-    indices: &'a Vec<usize>,
+    variation: &'a BoardPieceVariation,
     x_range: usize,
     y_range: usize,
-    board_width: usize,
+    rows: usize,
+    cols: usize,
     x: usize,
     y: usize,
 }
@@ -38,8 +77,8 @@ impl<'a> Iterator for TranslationsIterator<'a> {
         if self.y == self.y_range {
             return None;
         }
-        let offset = self.x + self.y * self.board_width;
-        let res = Some((offset, Translation { indices: self.indices, offset: offset, i: 0 }));
+        let offset = self.x + self.y * self.cols;
+        let res = Some((offset, Translation::new(self.variation, self.x, self.y, self.rows, self.cols)));
         if self.x + 1 == self.x_range {
             self.x = 0;
             self.y += 1;
@@ -50,47 +89,45 @@ impl<'a> Iterator for TranslationsIterator<'a> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct BoardPieceVariation {
-    points: Vec<usize>,
+    /// `(x, y)` within the bounding box.
+    points: Vec<(usize, usize)>,
+    /// The points as offsets from the top left corner on the board.
+    indices: Vec<usize>,
     width: usize,
     height: usize,
 }
 
 impl BoardPieceVariation {
     fn new(points: &[(i8, i8)], board_width: usize) -> Self {
-        let mut width = 0;
-        let mut height = 0;
-        let mut indices = Vec::new();
-        for &(x, y) in points {
-            width = width.max(x as usize + 1);
-            height = height.max(y as usize + 1);
-            indices.push(x as usize + y as usize * board_width);
-        }
+        let points = points.iter().map(|&(x, y)| (x as usize, y as usize)).collect::<Vec<_>>();
         BoardPieceVariation {
-            points: indices,
-            width: width,
-            height: height,
+            indices: points.iter().map(|&(x, y)| x + y * board_width).collect(),
+            width: points.iter().map(|&(x, _)| x + 1).max().unwrap_or(0),
+            height: points.iter().map(|&(_, y)| y + 1).max().unwrap_or(0),
+            points,
         }
     }
 
-    fn translations(&self, board_width: usize, board_height: usize) -> TranslationsIterator {
+    /// Every placement on the board, which may cross the edges that wrap around.
+    fn translations(&self, board_width: usize, board_height: usize, topology: Topology) -> TranslationsIterator<'_> {
+        // No translations at all if the piece is too big for the board.
+        let fits = self.width <= board_width && self.height <= board_height;
+        let range = |size: usize, extent: usize, wrap: bool| if wrap { size } else { size + 1 - extent };
         TranslationsIterator {
-            indices: &self.points,
-            x_range: board_width - self.width + 1,
-            y_range: board_height - self.height + 1,
-            board_width: board_width,
+            variation: self,
+            x_range: if fits { range(board_width, self.width, topology.wrap_x) } else { 0 },
+            y_range: if fits { range(board_height, self.height, topology.wrap_y) } else { 0 },
+            rows: board_height,
+            cols: board_width,
             x: 0,
             y: 0,
         }
     }
 
-    fn translation(&self, d: usize) -> Translation {
-        Translation {
-            indices: &self.points,
-            offset: d,
-            i: 0,
-        }
+    fn translation(&self, anchor: usize, board_width: usize, board_height: usize) -> Translation<'_> {
+        Translation::new(self, anchor % board_width, anchor / board_width, board_height, board_width)
     }
 }
 
@@ -102,190 +139,1232 @@ fn test_translations() {
     let v = BoardPieceVariation::new(points, board_width);
     assert_eq!(v.width, 2);
     assert_eq!(v.height, 3);
-    let translations = v.translations(board_width, board_height).map(|(o, p)| o).collect::<Vec<_>>();
+    let translations = v.translations(board_width, board_height, Topology::PLANE).map(|(o, _p)| o).collect::<Vec<_>>();
     assert_eq!(translations, vec![0, 1, 2, 4, 5, 6, 8, 9, 10]);
+    assert_eq!(v.translations(1, board_height, Topology::PLANE).count(), 0);
+    assert_eq!(v.translations(board_width, 2, Topology::PLANE).count(), 0);
+    assert_eq!(v.translations(board_width, board_height, Topology::CYLINDER).count(), 12);
+    assert_eq!(v.translations(board_width, board_height, Topology::TORUS).count(), 20);
+    // On a torus the piece anchored at the bottom right corner covers all four corners.
+    let cells = v.translation(19, board_width, board_height).collect::<Vec<_>>();
+    assert_eq!(cells, vec![19, 3, 7, 16]);
 }
 
 type BoardPiece = Vec<BoardPieceVariation>;
 
-#[derive(Debug)]
+/// What a cell of the board is, apart from the piece that may cover it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Terrain {
+    /// A cell that pieces may cover.
+    Open,
+    /// A cell that is not part of the board, such as a cell outside an irregular
+    /// outline; it is nobody's neighbor.
+    Hole,
+    /// A cell filled before the game, by no color; it blocks pieces and empty regions
+    /// but is still a neighbor of the cells around it.
+    Neutral,
+}
+
+/// Which cells the first piece of a color may cover; later pieces touch
+/// the corners of the pieces before.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum StartRule {
+    /// The home of the color.
+    #[default]
+    Home,
+    /// Any of the given cells, as `(x, y)`, shared by all colors.
+    AnyOf(Vec<Point>),
+    /// Any open cell on the edge of the board, where a side of the cell
+    /// has no neighbor.
+    Edge,
+    /// Any open cell.
+    Anywhere,
+}
+
+impl StartRule {
+    /// Parse `home`, `edge`, `anywhere` or `cells:X,Y;X,Y;...`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "home" => return Ok(StartRule::Home),
+            "edge" => return Ok(StartRule::Edge),
+            "anywhere" => return Ok(StartRule::Anywhere),
+            _ => {},
+        }
+        let error = || format!("invalid start rule {:?}", s);
+        if !s.starts_with("cells:") {
+            return Err(error());
+        }
+        s["cells:".len()..].split(';').map(|point| {
+            let mut coordinates = point.split(',').map(|v| v.trim().parse::<i8>());
+            match (coordinates.next(), coordinates.next(), coordinates.next()) {
+                (Some(Ok(x)), Some(Ok(y)), None) => Ok((x, y)),
+                _ => Err(error()),
+            }
+        }).collect::<Result<Vec<_>, _>>().map(StartRule::AnyOf)
+    }
+}
+
+/// How pieces may be placed; the default is the classic rules, where the first
+/// piece of a color covers its home and the next ones touch the corner of a
+/// piece of the same color but never its side.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Rules {
+    pub start: StartRule,
+    /// Pieces may touch the side of a piece of their own team, which then
+    /// connects them as a corner does.
+    pub edge_contact: bool,
+    /// Pieces may connect to the corner of a piece of any color.
+    pub any_corner: bool,
+    /// `teams[c]` is the team of color `c`; colors of a team count as one color
+    /// for contacts. If empty, each color is its own team.
+    pub teams: Vec<usize>,
+}
+
+/// A symmetry of the board dimensions that maps the set of homes onto itself.
+#[derive(Debug, Clone)]
+pub struct Symmetry {
+    pub transform: Transform,
+    /// `cells[i]` is the image of cell `i`.
+    cells: Vec<usize>,
+    /// `colors[c]` is the color whose home is the image of the home of `c`.
+    colors: Vec<Color>,
+}
+
+impl Symmetry {
+    /// The image of cell `i`.
+    pub fn cell(&self, i: usize) -> usize {
+        self.cells[i]
+    }
+
+    /// The color whose home is the image of the home of `color`.
+    pub fn color(&self, color: Color) -> Color {
+        self.colors[color as usize]
+    }
+
+    fn image(&self, board: &[Option<Color>]) -> Vec<Option<Color>> {
+        let mut res = vec![None; board.len()];
+        for (i, &c) in board.iter().enumerate() {
+            res[self.cells[i]] = c.map(|c| self.colors[c as usize]);
+        }
+        res
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Board {
     pieces: Vec<BoardPiece>,
     rows: usize,
     cols: usize,
-    /// `positions[c][p]` is `Some((i, offset))`
-    /// if color `c` is at `pieces[p][i].translation(offset)`
-    positions: Vec<Vec<Option<(usize, usize)>>>,
+    /// `stock[c][p]` is the number of copies of piece `p` that color `c` has not placed yet.
+    stock: Vec<Vec<usize>>,
     board: Vec<Option<Color>>,
+    terrain: Vec<Terrain>,
+    topology: Topology,
+    /// `flags[c * size + i]` holds the `CORNER` and `BLOCKED` flags of cell `i` for color `c`,
+    /// so that generating the moves of a color reads a single plane.
     flags: Vec<u8>,
     history: Vec<(Color, usize, usize, usize)>,
+    /// `placed[c]` is the number of pieces color `c` has placed.
+    placed: Vec<usize>,
     homes: Vec<usize>,
+    rules: Rules,
+    /// `teammates[c]` holds the colors in the team of color `c`, including itself.
+    teammates: Vec<Vec<Color>>,
+    /// Whether the pieces of a color may connect other colors, which
+    /// must still place their first piece on a start cell.
+    shares_contacts: bool,
+    /// `starts[c]` holds the cells, in order, that the first piece of color `c` may cover.
+    starts: Vec<Vec<usize>>,
+    /// The symmetry group of the empty board; always contains the identity first.
+    symmetries: Vec<Symmetry>,
 }
 
+/// The cell touches a piece that the color may connect to, by default diagonally
+/// to a piece of its team, or is a start cell of the color before it places
+/// its first piece.
 const CORNER: u8 = 1;
+/// The cell is not vacant or, unless edge contact is allowed, next to a piece
+/// of the team of the color.
 const BLOCKED: u8 = 2;
-const OCCUPIED: u8 = 2 + 8 + 32 + 128;
+
+/// Largest number of colors on a board; one value of `Color` is left
+/// free so that files can mark empty cells with 255.
+pub const MAX_COLORS: usize = 255;
 
 pub struct Moves<'a> {
     board: &'a mut Board,
     color: Color,
-    pub moves: Vec<(usize, usize, usize)>,
+    pub moves: Vec<Move>,
 }
 
 impl Board {
-    pub fn new(pieces: Vec<Piece>, rows: usize, cols: usize, home_points: &[Point]) -> Self {
+    /// A `rows` by `cols` board where color `c` starts at `home_points[c]`,
+    /// given as `(x, y)`, and every color plays with all of `pieces`.
+    pub fn new(pieces: Vec<Piece>, rows: usize, cols: usize, home_points: &[Point]) -> Result<Self, BlokusError> {
+        Self::with_terrain(pieces, rows, cols, home_points, vec![Terrain::Open; rows * cols])
+    }
+
+    /// Like `new`, with `terrain[i]` telling what cell `i` is; homes must be open.
+    pub fn with_terrain(pieces: Vec<Piece>, rows: usize, cols: usize, home_points: &[Point],
+                        terrain: Vec<Terrain>) -> Result<Self, BlokusError> {
+        if rows == 0 || cols == 0 {
+            return Err(BlokusError::BoardTooSmall { cells: rows * cols, needed: 1 });
+        }
+        if terrain.len() != rows * cols {
+            return Err(BlokusError::BadMap(format!("{} cells of terrain for a {}x{} board", terrain.len(), cols, rows)));
+        }
+        if home_points.len() > MAX_COLORS {
+            return Err(BlokusError::TooManyColors { colors: home_points.len(), max: MAX_COLORS });
+        }
+        let outside = |x: i8, y: i8| x < 0 || y < 0 || x as usize >= cols || y as usize >= rows;
+        if let Some(&home) = home_points.iter()
+            .find(|&&(x, y)| outside(x, y) || terrain[x as usize + y as usize * cols] != Terrain::Open) {
+            return Err(BlokusError::BadHome(home));
+        }
+        if let Some((_, &home)) = home_points.iter().enumerate().find(|&(c, h)| home_points[..c].contains(h)) {
+            return Err(BlokusError::BadHome(home));
+        }
         let homes = home_points.iter().map(|&(x, y)| x as usize + y as usize * cols).collect::<Vec<_>>();
-        assert!(homes.iter().all(|&i| i < rows * cols));
-        assert!(homes.len() <= 4);
-        let positions = vec![vec![None; pieces.len()]; homes.len()];
-        let flags = vec![0; rows * cols];
+        let stock = vec![vec![1; pieces.len()]; homes.len()];
+        let flags = vec![0; rows * cols * homes.len()];
         let mut res = Self {
             pieces: pieces.into_iter().map(|p| p.variations().into_iter().map(|v| BoardPieceVariation::new(&v.points, cols)).collect()).collect(),
-            rows: rows,
-            cols: cols,
-            positions: positions,
+            rows,
+            cols,
+            stock,
             board: vec![None; rows * cols],
-            flags: flags,
+            terrain,
+            topology: Topology::PLANE,
+            flags,
             history: Vec::new(),
-            homes: homes,
+            placed: vec![0; homes.len()],
+            homes,
+            rules: Rules::default(),
+            teammates: Vec::new(),
+            shares_contacts: false,
+            starts: Vec::new(),
+            symmetries: Vec::new(),
         };
-        res.update_home_flags();
+        res.prepare()?;
+        Ok(res)
+    }
+
+    /// A board drawn as an ASCII map with a line per row: `.` for an open cell,
+    /// `#` for a neutral cell, a space or `_` for a hole, and a digit `c` for
+    /// the open cell where color `c` starts. Lines shorter than the longest are
+    /// padded with holes, and blank lines before and after the map are ignored.
+    pub fn from_map(pieces: Vec<Piece>, map: &str) -> Result<Self, BlokusError> {
+        let lines = map.lines().map(|l| l.trim_end_matches('\r')).collect::<Vec<_>>();
+        let first = lines.iter().position(|l| !l.trim().is_empty()).unwrap_or(0);
+        let last = lines.iter().rposition(|l| !l.trim().is_empty()).map_or(0, |l| l + 1);
+        let lines = &lines[first..last.max(first)];
+        let rows = lines.len();
+        let cols = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        if rows > i8::MAX as usize || cols > i8::MAX as usize {
+            return Err(BlokusError::BadMap(format!("the map is larger than {} cells across", i8::MAX)));
+        }
+        let mut terrain = vec![Terrain::Hole; rows * cols];
+        let mut homes = Vec::new();
+        for (y, line) in lines.iter().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                terrain[x + y * cols] = match c {
+                    '.' => Terrain::Open,
+                    '#' => Terrain::Neutral,
+                    ' ' | '_' => Terrain::Hole,
+                    '0'..='9' => {
+                        homes.push((c.to_digit(10).unwrap() as usize, (x as i8, y as i8)));
+                        Terrain::Open
+                    },
+                    _ => return Err(BlokusError::BadMap(format!("unexpected {:?} in line {}", c, first + y + 1))),
+                };
+            }
+        }
+        homes.sort_unstable();
+        if homes.iter().enumerate().any(|(c, &(d, _))| c != d) {
+            return Err(BlokusError::BadMap("the homes are not numbered 0, 1, 2, ... once each".to_string()));
+        }
+        let homes = homes.into_iter().map(|(_, home)| home).collect::<Vec<_>>();
+        Self::with_terrain(pieces, rows, cols, &homes, terrain)
+    }
+
+    /// The same empty board with the edges joined as in `topology`.
+    /// An axis that wraps around needs at least three cells, so that
+    /// no cell is its own neighbor.
+    pub fn with_topology(mut self, topology: Topology) -> Result<Self, BlokusError> {
+        if !self.history.is_empty() {
+            return Err(BlokusError::BoardInPlay);
+        }
+        if topology.wrap_x && self.cols < 3 {
+            return Err(BlokusError::CannotWrap(self.cols));
+        }
+        if topology.wrap_y && self.rows < 3 {
+            return Err(BlokusError::CannotWrap(self.rows));
+        }
+        self.topology = topology;
+        self.prepare()?;
+        Ok(self)
+    }
+
+    /// The same empty board where the first piece of each color covers
+    /// a cell allowed by `rule` instead of its home.
+    pub fn with_start_rule(self, rule: StartRule) -> Result<Self, BlokusError> {
+        let rules = Rules { start: rule, ..self.rules.clone() };
+        self.with_rules(rules)
+    }
+
+    /// The same empty board played with `rules`.
+    pub fn with_rules(mut self, rules: Rules) -> Result<Self, BlokusError> {
+        if !self.history.is_empty() {
+            return Err(BlokusError::BoardInPlay);
+        }
+        if !rules.teams.is_empty() && rules.teams.len() != self.colors() {
+            return Err(BlokusError::BadTeams { teams: rules.teams.len(), colors: self.colors() });
+        }
+        self.rules = rules;
+        self.prepare()?;
+        Ok(self)
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    /// Whether colors `a` and `b` play in the same team.
+    pub fn same_team(&self, a: Color, b: Color) -> bool {
+        self.teammates[a as usize].contains(&b)
+    }
+
+    /// Compute the teams, the start cells, the symmetries and the flags of the
+    /// empty board after its shape, its rules or its piece sets are set.
+    fn prepare(&mut self) -> Result<(), BlokusError> {
+        let colors = self.colors();
+        let teams = &self.rules.teams;
+        let team = |c: usize| teams.get(c).cloned().unwrap_or(c);
+        self.teammates = (0..colors)
+            .map(|c| (0..colors).filter(|&d| team(d) == team(c)).map(|d| d as Color).collect())
+            .collect();
+        self.shares_contacts = self.rules.any_corner || self.teammates.iter().any(|team| team.len() > 1);
+        let size = self.board.len();
+        let open = (0..size).filter(|&i| self.terrain[i] == Terrain::Open);
+        let shared = match self.rules.start {
+            StartRule::Home => None,
+            StartRule::AnyOf(ref points) => {
+                let mut cells = Vec::new();
+                for &(x, y) in points.iter() {
+                    let inside = x >= 0 && y >= 0 && (x as usize) < self.cols && (y as usize) < self.rows;
+                    if !inside {
+                        return Err(BlokusError::BadStart((x, y)));
+                    }
+                    let i = x as usize + y as usize * self.cols;
+                    if self.terrain[i] != Terrain::Open {
+                        return Err(BlokusError::BadStart((x, y)));
+                    }
+                    cells.push(i);
+                }
+                cells.sort_unstable();
+                cells.dedup();
+                Some(cells)
+            },
+            StartRule::Edge => Some(open.filter(|&i| self.cardinal_neighbors(i).count() < 4).collect()),
+            StartRule::Anywhere => Some(open.collect()),
+        };
+        // An edge rule on a torus, say, or a map without open cells.
+        if shared.as_ref().map_or(false, |cells| cells.is_empty()) {
+            return Err(BlokusError::NoStartCells);
+        }
+        self.starts = match shared {
+            Some(cells) => vec![cells; self.colors()],
+            None => self.homes.iter().map(|&h| vec![h]).collect(),
+        };
+        self.symmetries = self.compute_symmetries();
+        for i in 0..size {
+            self.update_flags(i);
+        }
+        self.update_start_flags();
+        Ok(())
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    /// The same empty board where color `c` plays with `sets[c][p]` copies of
+    /// piece `p` instead of one of each; the pieces a color has no copy of are
+    /// left out of its set.
+    pub fn with_piece_sets(mut self, sets: Vec<Vec<usize>>) -> Result<Self, BlokusError> {
+        if !self.history.is_empty() {
+            return Err(BlokusError::BoardInPlay);
+        }
+        if sets.len() != self.colors() {
+            return Err(BlokusError::BadPieceSets(format!("{} sets for {} colors", sets.len(), self.colors())));
+        }
+        if let Some((c, set)) = sets.iter().enumerate().find(|&(_, set)| set.len() != self.pieces.len()) {
+            return Err(BlokusError::BadPieceSets(
+                format!("the set of color {} counts {} pieces instead of {}", c, set.len(), self.pieces.len())));
+        }
+        self.stock = sets;
+        self.prepare()?;
+        Ok(self)
+    }
+
+    fn compute_symmetries(&self) -> Vec<Symmetry> {
+        let (rows, cols, homes, terrain, topology) = (self.rows, self.cols, &self.homes, &self.terrain, self.topology);
+        let mut res = Vec::new();
+        for &transform in TRANSFORMS.iter() {
+            if !transform.fits(rows, cols) {
+                continue;
+            }
+            // Swapping the axes would swap the edges that wrap around with those that do not.
+            if transform.swaps_axes() && topology.wrap_x != topology.wrap_y {
+                continue;
+            }
+            let cells = transform.cell_map(rows, cols);
+            if (0..cells.len()).any(|i| terrain[cells[i]] != terrain[i]) {
+                continue;
+            }
+            let colors = homes.iter()
+                .map(|&h| homes.iter().position(|&g| g == cells[h]).map(|c| c as Color))
+                .collect::<Option<Vec<_>>>();
+            // The start cells of each color must go to those of its image.
+            let maps_starts = |colors: &[Color]| self.starts.iter().enumerate().all(|(c, starts)| {
+                let mut image = starts.iter().map(|&i| cells[i]).collect::<Vec<_>>();
+                image.sort_unstable();
+                image == self.starts[colors[c] as usize]
+            });
+            // So must the teams.
+            let maps_teams = |colors: &[Color]| (0..homes.len()).all(|c| {
+                (0..homes.len()).all(|d| self.same_team(c as Color, d as Color) == self.same_team(colors[c], colors[d]))
+            });
+            // And each color must have the same pieces as its image.
+            let maps_stock = |colors: &[Color]| {
+                self.stock.iter().enumerate().all(|(c, set)| *set == self.stock[colors[c] as usize])
+            };
+            if let Some(colors) = colors.filter(|colors| maps_starts(colors) && maps_teams(colors) && maps_stock(colors)) {
+                res.push(Symmetry { transform, cells, colors });
+            }
+        }
         res
     }
 
-    fn update_home_flags(&mut self) {
-        for (c, i) in self.homes.iter().enumerate() {
-            if (self.flags[*i] & (BLOCKED << (2 * c))) == 0 {
-                self.flags[*i] |= CORNER << (2 * c);
+    pub fn symmetries(&self) -> &[Symmetry] {
+        &self.symmetries
+    }
+
+    /// The symmetries other than the identity that keep every color in place.
+    fn color_fixing_symmetries(&self) -> impl Iterator<Item = &Symmetry> {
+        self.symmetries.iter()
+            .filter(|s| s.transform != Transform::Identity)
+            .filter(|s| s.colors.iter().enumerate().all(|(c, &d)| c == d as usize))
+    }
+
+    /// Whether some symmetry other than the identity keeps every color in place,
+    /// so that `Moves::dedup_symmetric` can remove moves. On the standard board
+    /// with four homes in the corners, every reflection or rotation moves a home
+    /// to that of another color, so none does.
+    pub fn has_color_fixing_symmetry(&self) -> bool {
+        self.color_fixing_symmetries().next().is_some()
+    }
+
+    /// The symmetries of `color_fixing_symmetries` that map the current
+    /// position onto itself.
+    fn stabilizer(&self) -> Vec<&Symmetry> {
+        self.color_fixing_symmetries().filter(|s| s.image(&self.board) == self.board).collect()
+    }
+
+    /// A key that is equal for positions that are mirror images or rotations
+    /// of each other, treating colors as interchangeable along with their homes.
+    pub fn canonical_key(&self) -> Vec<u8> {
+        self.symmetries.iter()
+            .map(|s| s.image(&self.board).into_iter().map(|c| c.map_or(0, |c| c + 1)).collect::<Vec<u8>>())
+            .min()
+            .unwrap()
+    }
+
+    /// Hash of `canonical_key`. The tree search does not merge transpositions,
+    /// so this only serves to recognize positions already seen, such as the
+    /// solutions the blokus binary has printed.
+    pub fn canonical_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.canonical_key().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Mark the free start cells of the colors without pieces on the board as corners.
+    fn update_start_flags(&mut self) {
+        let size = self.board.len();
+        let placed = &self.placed;
+        for (c, starts) in self.starts.iter().enumerate().filter(|&(c, _)| placed[c] == 0) {
+            for &i in starts.iter() {
+                let flag = &mut self.flags[c * size + i];
+                if *flag & BLOCKED == 0 {
+                    *flag |= CORNER;
+                }
             }
         }
     }
 
+    fn flag(&self, color: Color, i: usize) -> u8 {
+        self.flags[color as usize * self.board.len() + i]
+    }
+
     pub fn size(&self) -> usize {
         self.board.len()
     }
 
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// The cells covered by a move.
+    pub fn cells(&self, (piece, variation, offset): Move) -> Vec<usize> {
+        self.translation(piece, variation, offset).collect()
+    }
+
+    fn translation(&self, piece: usize, variation: usize, anchor: usize) -> Translation<'_> {
+        self.pieces[piece][variation].translation(anchor, self.cols, self.rows)
+    }
+
+    /// Number of colors, one per home.
+    pub fn colors(&self) -> usize {
+        self.homes.len()
+    }
+
+    /// Whether `color` may cover cell `i` as part of a move:
+    /// it is empty and not next to a piece of that color.
+    pub fn is_free(&self, color: Color, i: usize) -> bool {
+        self.flag(color, i) & BLOCKED == 0
+    }
+
+    /// Whether cell `i` is a free cell diagonal to `color` (or its unused home),
+    /// so that a move of `color` may cover it.
+    pub fn is_corner(&self, color: Color, i: usize) -> bool {
+        self.flag(color, i) & (CORNER | BLOCKED) == CORNER
+    }
+
+    /// Total number of squares in the pieces `color` has not placed yet.
+    pub fn remaining_squares(&self, color: Color) -> usize {
+        self.stock[color as usize].iter().enumerate()
+            .map(|(piece, &copies)| copies * self.piece_size(piece))
+            .sum()
+    }
+
+    /// The pieces placed so far, as `(color, piece, variation, offset)`.
+    pub fn history(&self) -> &[(Color, usize, usize, usize)] {
+        &self.history
+    }
+
+    /// Number of different pieces on the board, whichever colors play with them.
+    pub fn piece_count(&self) -> usize {
+        self.pieces.len()
+    }
+
+    /// Whether `color` has a copy of `piece` left to place.
+    pub fn has_piece(&self, color: Color, piece: usize) -> bool {
+        self.stock[color as usize][piece] > 0
+    }
+
+    /// Number of copies of `piece` that `color` has left to place.
+    pub fn copies_left(&self, color: Color, piece: usize) -> usize {
+        self.stock[color as usize][piece]
+    }
+
+    /// The move that places `variation` of `piece` with the top left corner
+    /// of its bounding box at cell `anchor`, if it stays on the board,
+    /// possibly across edges that wrap around. The move is not necessarily legal.
+    pub fn move_at(&self, piece: usize, variation: usize, anchor: usize) -> Option<Move> {
+        let v = self.pieces.get(piece)?.get(variation)?;
+        let (x, y) = (anchor % self.cols, anchor / self.cols);
+        if y >= self.rows || v.width > self.cols || v.height > self.rows {
+            return None;
+        }
+        if (!self.topology.wrap_x && x + v.width > self.cols) || (!self.topology.wrap_y && y + v.height > self.rows) {
+            return None;
+        }
+        Some((piece, variation, anchor))
+    }
+
+    /// The placement of `piece` that covers exactly `cells`, legal or not.
+    pub fn find_move(&self, piece: usize, cells: &[usize]) -> Option<Move> {
+        let mut shape = cells.to_vec();
+        shape.sort_unstable();
+        let &first = shape.first()?;
+        let (x, y) = (first % self.cols, first / self.cols);
+        for (variation, v) in self.pieces.get(piece)?.iter().enumerate() {
+            if v.points.len() != shape.len() {
+                continue;
+            }
+            // Try the anchors that put each point of the piece on the first cell.
+            for &(px, py) in v.points.iter() {
+                let ax = (x + self.cols - px % self.cols) % self.cols;
+                let ay = (y + self.rows - py % self.rows) % self.rows;
+                if let Some(mv) = self.move_at(piece, variation, ax + ay * self.cols) {
+                    let mut covered = self.cells(mv);
+                    covered.sort_unstable();
+                    if covered == shape {
+                        return Some(mv);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Number of squares in the given piece.
+    pub fn piece_size(&self, piece: usize) -> usize {
+        self.pieces[piece][0].points.len()
+    }
+
+    /// Number of new corner cells that `color` would gain by playing `mv`:
+    /// empty cells diagonal to the piece that are not next to the piece
+    /// or to another piece of the same color and were not corners before.
+    pub fn corner_gain(&self, color: Color, (piece, variation, offset): Move) -> usize {
+        let cells = self.translation(piece, variation, offset).collect::<Vec<_>>();
+        let mut gained = Vec::new();
+        for &i in cells.iter() {
+            for j in self.diagonal_neighbors(i) {
+                if self.flag(color, j) != 0 || cells.contains(&j) || gained.contains(&j) {
+                    continue;
+                }
+                if self.cardinal_neighbors(j).any(|k| cells.contains(&k)) {
+                    continue;
+                }
+                gained.push(j);
+            }
+        }
+        gained.len()
+    }
+
+    /// The color covering cell `i`, or `None` if no piece covers it.
     pub fn at(&self, i: usize) -> Option<Color> {
         self.board[i]
     }
 
-    pub fn diagonal_neighbors(&self, position: usize) -> DiagonalIterator {
+    pub fn terrain(&self, i: usize) -> Terrain {
+        self.terrain[i]
+    }
+
+    /// Whether cell `i` is open and not covered by a piece.
+    pub fn is_vacant(&self, i: usize) -> bool {
+        self.terrain[i] == Terrain::Open && self.board[i].is_none()
+    }
+
+    /// The neighbors of a cell across corners, without holes.
+    pub fn diagonal_neighbors(&self, position: usize) -> DiagonalIterator<'_> {
         DiagonalIterator::new(
             self.rows,
             self.cols,
             position % self.cols,
             position / self.cols,
+            &self.terrain,
+            self.topology,
         )
     }
 
-    pub fn cardinal_neighbors(&self, position: usize) -> CardinalIterator {
+    /// The neighbors of a cell across sides, without holes.
+    pub fn cardinal_neighbors(&self, position: usize) -> CardinalIterator<'_> {
         CardinalIterator::new(
             self.rows,
             self.cols,
             position % self.cols,
             position / self.cols,
+            &self.terrain,
+            self.topology,
         )
     }
 
-    fn compute_flag(&self, i: usize) -> u8 {
-        if self.board[i] != None {
-            return OCCUPIED;
+    /// Recompute the flags of cell `i` for every color from its neighbors;
+    /// cells that are not vacant are blocked for all.
+    fn update_flags(&mut self, i: usize) {
+        let size = self.board.len();
+        let blocked = if self.is_vacant(i) { 0 } else { BLOCKED };
+        for c in 0..self.homes.len() {
+            self.flags[c * size + i] = blocked;
         }
-        let mut flag = 0;
-        for j in self.cardinal_neighbors(i) {
-            match self.board[j] {
-                Some(c) => flag |= BLOCKED << (2 * c),
-                None => (),
-            };
+        if blocked != 0 {
+            return;
         }
-        for j in self.diagonal_neighbors(i) {
-            match self.board[j] {
-                Some(c) => flag |= CORNER << (2 * c),
-                None => (),
-            };
+        let (x, y) = (i % self.cols, i / self.cols);
+        let side = if self.rules.edge_contact { CORNER } else { BLOCKED };
+        for j in CardinalIterator::new(self.rows, self.cols, x, y, &self.terrain, self.topology) {
+            if let Some(d) = self.board[j] {
+                for &c in self.teammates[d as usize].iter() {
+                    self.flags[c as usize * size + i] |= side;
+                }
+            }
+        }
+        for j in DiagonalIterator::new(self.rows, self.cols, x, y, &self.terrain, self.topology) {
+            if let Some(d) = self.board[j] {
+                if self.rules.any_corner {
+                    for c in 0..self.homes.len() {
+                        self.flags[c * size + i] |= CORNER;
+                    }
+                } else {
+                    for &c in self.teammates[d as usize].iter() {
+                        self.flags[c as usize * size + i] |= CORNER;
+                    }
+                }
+            }
         }
-        flag
+        if self.shares_contacts {
+            // Only start cells connect the first piece of a color.
+            for c in 0..self.homes.len() {
+                if self.placed[c] == 0 {
+                    self.flags[c * size + i] &= !CORNER;
+                }
+            }
+        }
+    }
+
+    pub fn moves(&mut self, color: Color) -> Result<Moves<'_>, BlokusError> {
+        let moves = self.legal_moves(color)?;
+        Ok(Moves { board: self, color, moves })
     }
 
-    pub fn moves(&mut self, color: Color) -> Result<Moves, &'static str> {
-        if color as usize >= self.positions.len() { return Err("color out of bounds"); }
+    /// The moves `color` can play, without borrowing the board for placing one.
+    pub fn legal_moves(&self, color: Color) -> Result<Vec<Move>, BlokusError> {
+        if color as usize >= self.colors() {
+            return Err(BlokusError::InvalidColor(color));
+        }
         let mut moves = Vec::new();
         for (piece, variations) in self.pieces.iter().enumerate() {
-            if !self.positions[color as usize][piece].is_none() {
+            if !self.has_piece(color, piece) {
                 continue;
             }
             for (variation, p) in variations.iter().enumerate() {
-                for (d, translation) in p.translations(self.cols, self.rows) {
-                    let mut flag_union = 0;
-                    for i in translation {
-                        flag_union |= self.flags[i];
-                    }
-                    let test_flags = (CORNER | BLOCKED) << (2*color);
-                    let req_flags = CORNER << (2*color);
-                    if (flag_union & test_flags) == req_flags {
+                for (d, translation) in p.translations(self.cols, self.rows, self.topology) {
+                    if self.fits(color, translation) {
                         moves.push((piece, variation, d));
                     }
                 }
             }
         }
-        Ok(Moves { board: self, color: color, moves: moves })
+        Ok(moves)
+    }
+
+    fn fits<I: Iterator<Item = usize>>(&self, color: Color, cells: I) -> bool {
+        let mut flag_union = 0;
+        for i in cells {
+            flag_union |= self.flag(color, i);
+        }
+        flag_union == CORNER
+    }
+
+    /// The move of `color` that places `variation` of `piece` with the top left
+    /// corner of its bounding box at cell `anchor`, as in `move_at`, if it is legal,
+    /// and otherwise the first rule it breaks: it must stay on the board, use
+    /// a piece not placed yet, cover only empty cells, not touch the side of
+    /// a piece of the same color, and cover the home of the color with the
+    /// first piece or touch the corner of a piece of the same color afterwards,
+    /// as varied by the `Rules` of the board.
+    pub fn check_move(&self, color: Color, piece: usize, variation: usize, anchor: usize) -> Result<Move, BlokusError> {
+        if color as usize >= self.colors() {
+            return Err(BlokusError::InvalidColor(color));
+        }
+        let variations = self.pieces.get(piece).ok_or(BlokusError::NoSuchPiece(piece))?;
+        if variation >= variations.len() {
+            return Err(BlokusError::NoSuchOrientation { piece, variation });
+        }
+        let mv = self.move_at(piece, variation, anchor).ok_or(BlokusError::OutOfBounds((piece, variation, anchor)))?;
+        if !self.has_piece(color, piece) {
+            return Err(BlokusError::PieceUsed(piece));
+        }
+        let translation = self.translation(piece, variation, anchor);
+        if let Some(i) = translation.clone().find(|&i| self.terrain[i] != Terrain::Open) {
+            return Err(BlokusError::ClosedCell(i));
+        }
+        if let Some(i) = translation.clone().find(|&i| self.board[i].is_some()) {
+            return Err(BlokusError::OccupiedCell(i));
+        }
+        if let Some(i) = translation.clone().find(|&i| !self.is_free(color, i)) {
+            return Err(BlokusError::EdgeContact(i));
+        }
+        if !translation.clone().any(|i| self.is_corner(color, i)) {
+            return Err(match self.rules.start {
+                _ if self.placed[color as usize] > 0 => BlokusError::NoCornerContact,
+                StartRule::Home => BlokusError::HomeNotCovered(self.homes[color as usize]),
+                _ => BlokusError::NoStartCell,
+            });
+        }
+        Ok(mv)
     }
 
-    fn write_piece(&mut self, piece: usize, variation: usize, offset: usize, prev: Option<Color>, next: Option<Color>) {
-        let mut first = None;
-        let mut last = 0;
-        for i in self.pieces[piece][variation].translation(offset) {
-            if first == None {
-                first = Some(i);
+    /// Place a piece as described by `check_move`, which may come from outside,
+    /// and return the move; the board is unchanged if it is not legal.
+    pub fn try_place(&mut self, color: Color, piece: usize, variation: usize, anchor: usize) -> Result<Move, BlokusError> {
+        let mv = self.check_move(color, piece, variation, anchor)?;
+        self.write_piece(piece, variation, anchor, None, Some(color))?;
+        self.stock[color as usize][piece] -= 1;
+        self.history.push((color, piece, variation, anchor));
+        self.placed[color as usize] += 1;
+        if self.placed[color as usize] == 1 {
+            if self.shares_contacts {
+                // The pieces of other colors now connect the color too.
+                for i in 0..self.board.len() {
+                    self.update_flags(i);
+                }
+            } else {
+                // The other start cells are no longer corners of the color.
+                for k in 0..self.starts[color as usize].len() {
+                    let i = self.starts[color as usize][k];
+                    self.update_flags(i);
+                }
             }
-            last = i;
-            assert_eq!(self.board[i], prev);
+        }
+        self.update_start_flags();
+        Ok(mv)
+    }
+
+    /// Place a move from `legal_moves(color)`; the board is unchanged if it is not legal.
+    pub fn place(&mut self, color: Color, (piece, variation, offset): Move) -> Result<(), BlokusError> {
+        self.try_place(color, piece, variation, offset).map(|_| ())
+    }
+
+    /// Replace `prev` by `next` in the cells of a piece, failing without
+    /// changes if one of them does not hold `prev`.
+    fn write_piece(&mut self, piece: usize, variation: usize, offset: usize,
+                   prev: Option<Color>, next: Option<Color>) -> Result<(), BlokusError> {
+        let cells = self.translation(piece, variation, offset).collect::<Vec<_>>();
+        if let Some(&i) = cells.iter().find(|&&i| self.board[i] != prev) {
+            return Err(BlokusError::OccupiedCell(i));
+        }
+        for &i in cells.iter() {
             self.board[i] = next;
         }
 
-        // Update flags
-        let first = first.unwrap();
-        let margin = self.cols + 1;
-        let a = if first < margin { 0 } else { first - margin };
-        let b = (last + margin + 1).min(self.flags.len());
-        for i in 0..(self.rows * self.cols) {
-            self.flags[i] = self.compute_flag(i);
+        // Update flags of the piece and the cells around it
+        let mut touched = cells.clone();
+        for &i in cells.iter() {
+            touched.extend(self.cardinal_neighbors(i).chain(self.diagonal_neighbors(i)));
+        }
+        for i in touched {
+            self.update_flags(i);
         }
-        self.update_home_flags();
+        Ok(())
     }
 
-    pub fn undo(&mut self) -> Result<(), &'static str> {
-        let (color, piece, variation, offset) = match self.history.pop() {
-            Some(x) => x,
-            None => return Err("no moves to undo"),
-        };
-        assert_eq!(self.positions[color as usize][piece], Some((variation, offset)));
-        self.positions[color as usize][piece] = None;
-        self.write_piece(piece, variation, offset, Some(color), None);
+    pub fn undo(&mut self) -> Result<(), BlokusError> {
+        let &(color, piece, variation, offset) = self.history.last().ok_or(BlokusError::NothingToUndo)?;
+        self.write_piece(piece, variation, offset, Some(color), None)?;
+        self.stock[color as usize][piece] += 1;
+        self.history.pop();
+        self.placed[color as usize] -= 1;
+        if self.placed[color as usize] == 0 && self.shares_contacts {
+            for i in 0..self.board.len() {
+                self.update_flags(i);
+            }
+        }
+        self.update_start_flags();
         Ok(())
     }
 }
 
+/// The ANSI foreground color of `color`: red, green, yellow, blue, magenta and
+/// cyan, then their bright variants, then the 6x6x6 cube of 256-color terminals.
+fn ansi_color(color: Color) -> String {
+    match color {
+        0..=5 => (31 + color).to_string(),
+        6..=11 => (91 + color - 6).to_string(),
+        _ => format!("38;5;{}", 16 + (color as usize - 12) * 37 % 216),
+    }
+}
+
 impl std::fmt::Display for Board {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         for y in 0..self.rows {
             for x in 0..self.cols {
-                match self.board[x + y * self.cols] {
-                    None => try!(write!(f, "\u{2591}\u{2591}")),
-                    Some(c) => try!(write!(f, "\x1B[{}m\u{2588}\u{2588}\x1B[0m", 31 + c)),
+                let i = x + y * self.cols;
+                match (self.terrain[i], self.board[i]) {
+                    (Terrain::Hole, _) => write!(f, "  ")?,
+                    (Terrain::Neutral, _) => write!(f, "\u{2593}\u{2593}")?,
+                    (Terrain::Open, None) => write!(f, "\u{2591}\u{2591}")?,
+                    (Terrain::Open, Some(c)) => write!(f, "\x1B[{}m\u{2588}\u{2588}\x1B[0m", ansi_color(c))?,
                 }
             }
-            try!(write!(f, "\n"));
+            writeln!(f)?;
         }
         Ok(())
     }
 }
 
 impl <'a> Moves<'a> {
-    pub fn place(self, move_index: usize) {
-        let (piece, variation, offset) = self.moves[move_index];
-        self.board.write_piece(piece, variation, offset, None, Some(self.color));
-        self.board.positions[self.color as usize][piece] = Some((variation, offset));
-        self.board.history.push((self.color, piece, variation, offset));
+    pub fn board(&self) -> &Board {
+        self.board
+    }
+
+    pub fn color(&self) -> Color {
+        self.color
+    }
+
+    fn cells(&self, move_index: usize) -> Vec<usize> {
+        self.board.cells(self.moves[move_index])
+    }
+
+    /// Remove moves that lead to a position symmetric to that of an earlier move.
+    /// Only symmetries that fix the current position and every color are used:
+    /// they keep the order of play, the pieces each color has left and any
+    /// objective that tells colors apart, so the remaining moves still reach
+    /// every position up to symmetry. Boards without such a symmetry, like the
+    /// standard one with four homes in the corners, keep all their moves;
+    /// see `Board::has_color_fixing_symmetry`.
+    pub fn dedup_symmetric(&mut self) {
+        let stabilizer = self.board.stabilizer().into_iter()
+            .map(|s| s.cells.clone())
+            .collect::<Vec<_>>();
+        if stabilizer.is_empty() {
+            return;
+        }
+        let mut seen = HashSet::new();
+        let mut keep = Vec::new();
+        for i in 0..self.moves.len() {
+            // Pieces of the same shape may differ, so the piece is part of the key.
+            let piece = self.moves[i].0;
+            let mut cells = self.cells(i);
+            cells.sort_unstable();
+            if seen.contains(&(piece, cells.clone())) {
+                continue;
+            }
+            for s in stabilizer.iter() {
+                let mut image = cells.iter().map(|&j| s[j]).collect::<Vec<_>>();
+                image.sort_unstable();
+                seen.insert((piece, image));
+            }
+            seen.insert((piece, cells));
+            keep.push(self.moves[i]);
+        }
+        self.moves = keep;
+    }
+
+    pub fn place(self, move_index: usize) -> Result<(), BlokusError> {
+        let count = self.moves.len();
+        let mv = *self.moves.get(move_index).ok_or(BlokusError::NoSuchMove { index: move_index, count })?;
+        self.board.place(self.color, mv)
+    }
+}
+
+#[cfg(test)]
+fn corner_board(rows: usize, cols: usize) -> Board {
+    let (w, h) = (cols as i8 - 1, rows as i8 - 1);
+    Board::new(::pieces::compute_pieces(3), rows, cols, &[(0, 0), (w, 0), (w, h), (0, h)]).unwrap()
+}
+
+#[test]
+fn test_symmetry_group() {
+    assert_eq!(corner_board(8, 8).symmetries().len(), 8);
+    assert_eq!(corner_board(6, 8).symmetries().len(), 4);
+    let b = Board::new(::pieces::compute_pieces(3), 8, 8, &[(0, 0), (3, 5)]).unwrap();
+    assert_eq!(b.symmetries().len(), 1);
+}
+
+#[test]
+fn test_corner_gain() {
+    let mut b = corner_board(8, 8);
+    let gains = {
+        let moves = b.moves(0).unwrap();
+        (0..moves.moves.len()).map(|i| {
+            let mut c = moves.cells(i);
+            c.sort_unstable();
+            (c, moves.board().corner_gain(0, moves.moves[i]))
+        }).collect::<Vec<_>>()
+    };
+    assert!(gains.contains(&(vec![0], 1)));
+    assert!(gains.contains(&(vec![0, 1], 1)));
+    assert!(gains.contains(&(vec![0, 1, 8], 2)));
+    place_cells(&mut b, 0, &[0]);
+    // The corner at 9 was already available after the monomino.
+    let moves = b.moves(0).unwrap();
+    let i = (0..moves.moves.len()).find(|&i| moves.cells(i) == vec![9, 10]).unwrap();
+    assert_eq!(moves.board().corner_gain(0, moves.moves[i]), 3);
+}
+
+#[cfg(test)]
+fn place_cells(board: &mut Board, color: Color, cells: &[usize]) {
+    let moves = board.moves(color).unwrap();
+    let i = (0..moves.moves.len()).find(|&i| {
+        let mut c = moves.cells(i);
+        c.sort_unstable();
+        c == cells
+    }).unwrap();
+    moves.place(i).unwrap();
+}
+
+/// Check that `check_move` accepts exactly the legal moves of `color`,
+/// trying every piece, variation and anchor.
+#[cfg(test)]
+fn assert_check_move_matches_legal(board: &Board, color: Color) {
+    let legal = board.legal_moves(color).unwrap();
+    for p in 0..board.piece_count() {
+        for v in 0..8 {
+            for anchor in 0..board.size() {
+                let ok = board.check_move(color, p, v, anchor).is_ok();
+                assert_eq!(ok, legal.contains(&(p, v, anchor)), "{} {} {}", p, v, anchor);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_canonical_key_of_mirror_images() {
+    let mut a = corner_board(8, 8);
+    let mut b = corner_board(8, 8);
+    // A horizontal and a vertical I-tromino in the corner are mirror images.
+    place_cells(&mut a, 0, &[0, 1, 2]);
+    place_cells(&mut b, 0, &[0, 8, 16]);
+    assert_eq!(a.canonical_key(), b.canonical_key());
+    assert_eq!(a.canonical_hash(), b.canonical_hash());
+    // Color 1 playing the same shape in its own corner is a rotation of the same position.
+    let mut c = corner_board(8, 8);
+    place_cells(&mut c, 1, &[5, 6, 7]);
+    assert_eq!(a.canonical_key(), c.canonical_key());
+    b.undo().unwrap();
+    place_cells(&mut b, 0, &[0, 1, 8]);
+    assert_ne!(a.canonical_key(), b.canonical_key());
+}
+
+#[test]
+fn test_dedup_symmetric_moves() {
+    // Every symmetry of the four-corner board moves some home to that of another
+    // color; the transpose swaps colors 1 and 3, which would change the order of play.
+    let mut b = corner_board(8, 8);
+    assert!(!b.has_color_fixing_symmetry());
+    let mut moves = b.moves(0).unwrap();
+    assert_eq!(moves.moves.len(), 8);
+    moves.dedup_symmetric();
+    assert_eq!(moves.moves.len(), 8);
+
+    // With homes on the diagonal, the transpose fixes both colors.
+    let mut b = Board::new(::pieces::compute_pieces(3), 8, 8, &[(0, 0), (7, 7)]).unwrap();
+    assert!(b.has_color_fixing_symmetry());
+    let mut moves = b.moves(0).unwrap();
+    moves.dedup_symmetric();
+    // Monomino, domino, I-tromino and two of the three L-tromino placements remain.
+    assert_eq!(moves.moves.len(), 5);
+    moves.place(4).unwrap();
+    // Color 0's piece breaks the symmetry.
+    let mut moves = b.moves(1).unwrap();
+    let count = moves.moves.len();
+    moves.dedup_symmetric();
+    assert_eq!(moves.moves.len(), count);
+}
+
+#[test]
+fn test_errors() {
+    let pieces = ::pieces::compute_pieces(2);
+    assert_eq!(Board::new(pieces.clone(), 0, 4, &[]).unwrap_err(), BlokusError::BoardTooSmall { cells: 0, needed: 1 });
+    assert_eq!(Board::new(pieces.clone(), 4, 4, &[(0, 0), (4, 0)]).unwrap_err(), BlokusError::BadHome((4, 0)));
+    assert_eq!(Board::new(pieces.clone(), 4, 4, &[(0, 0), (3, 3), (0, 0)]).unwrap_err(), BlokusError::BadHome((0, 0)));
+    assert_eq!(Board::new(pieces.clone(), 4, 4, &[(0, 0); 256]).unwrap_err(), BlokusError::TooManyColors { colors: 256, max: 255 });
+    let mut b = Board::new(pieces, 4, 4, &[(0, 0), (3, 3)]).unwrap();
+    assert_eq!(b.undo(), Err(BlokusError::NothingToUndo));
+    assert_eq!(b.legal_moves(2).unwrap_err(), BlokusError::InvalidColor(2));
+    let domino = b.find_move(1, &[0, 1]).unwrap();
+    b.place(0, domino).unwrap();
+    assert_eq!(b.place(0, domino), Err(BlokusError::PieceUsed(1)));
+    let moves = b.moves(1).unwrap();
+    let count = moves.moves.len();
+    assert_eq!(moves.place(count), Err(BlokusError::NoSuchMove { index: count, count }));
+    assert_eq!(b.history().len(), 1);
+    b.undo().unwrap();
+    assert!(b.history().is_empty() && b.at(0).is_none());
+}
+
+#[test]
+fn test_try_place() {
+    // Color 0 starts at the top left and color 1 at the bottom right of a 5x5 board.
+    let mut b = Board::new(::pieces::compute_pieces(3), 5, 5, &[(0, 0), (4, 4)]).unwrap();
+    let piece = |b: &Board, cells: &[usize]| (0..b.piece_count()).find_map(|p| b.find_move(p, cells)).unwrap();
+    let (bar, h, _) = piece(&b, &[0, 1, 2]);
+    assert_eq!(b.try_place(2, bar, h, 0), Err(BlokusError::InvalidColor(2)));
+    assert_eq!(b.try_place(0, 99, 0, 0), Err(BlokusError::NoSuchPiece(99)));
+    assert_eq!(b.try_place(0, bar, 99, 0), Err(BlokusError::NoSuchOrientation { piece: bar, variation: 99 }));
+    assert_eq!(b.try_place(0, bar, h, 3), Err(BlokusError::OutOfBounds((bar, h, 3))));
+    assert_eq!(b.try_place(0, bar, h, 25), Err(BlokusError::OutOfBounds((bar, h, 25))));
+    assert_eq!(b.try_place(0, bar, h, 1), Err(BlokusError::HomeNotCovered(0)));
+    assert_eq!(b.try_place(0, bar, h, 0), Ok((bar, h, 0)));
+    assert_eq!(b.try_place(0, bar, h, 5), Err(BlokusError::PieceUsed(bar)));
+    let (mono, v, _) = piece(&b, &[0]);
+    assert_eq!(b.try_place(0, mono, v, 2), Err(BlokusError::OccupiedCell(2)));
+    assert_eq!(b.try_place(0, mono, v, 5), Err(BlokusError::EdgeContact(5)));
+    assert_eq!(b.try_place(0, mono, v, 10), Err(BlokusError::NoCornerContact));
+    assert_eq!(b.try_place(1, mono, v, 8), Err(BlokusError::HomeNotCovered(24)));
+    // Every rejection left the board as it was.
+    assert_eq!(b.history(), &[(0, bar, h, 0)]);
+    assert_eq!(b.try_place(0, mono, v, 8), Ok((mono, v, 8)));
+    assert_check_move_matches_legal(&b, 1);
+}
+
+#[test]
+fn test_from_map() {
+    let pieces = ::pieces::compute_pieces(3);
+    let b = Board::from_map(pieces.clone(), "\n0..\n.#.\n_.1\n\n").unwrap();
+    assert_eq!((b.rows(), b.cols(), b.colors()), (3, 3, 2));
+    assert_eq!((b.terrain(4), b.terrain(6), b.terrain(8)), (Terrain::Neutral, Terrain::Hole, Terrain::Open));
+    assert_eq!(b.cardinal_neighbors(3).collect::<Vec<_>>(), vec![0, 4]);
+    assert_eq!(b.diagonal_neighbors(3).collect::<Vec<_>>(), vec![1, 7]);
+    assert!(Board::with_terrain(pieces.clone(), 3, 3, &[(0, 0)], vec![Terrain::Open; 8]).is_err());
+    for c in 0..2 {
+        for (p, v, anchor) in b.legal_moves(c).unwrap() {
+            assert!(b.translation(p, v, anchor).all(|i| b.is_vacant(i)));
+        }
+    }
+    let mono = (0..b.piece_count()).find_map(|p| b.find_move(p, &[0])).unwrap();
+    assert_eq!(b.check_move(0, mono.0, mono.1, 4), Err(BlokusError::ClosedCell(4)));
+    assert_eq!(b.check_move(0, mono.0, mono.1, 6), Err(BlokusError::ClosedCell(6)));
+    // Short lines are padded with holes.
+    let b = Board::from_map(pieces.clone(), "0.\n.").unwrap();
+    assert_eq!(b.terrain(3), Terrain::Hole);
+    assert!(Board::from_map(pieces.clone(), "0.x").is_err());
+    assert!(Board::from_map(pieces.clone(), "1..").is_err());
+    assert!(Board::from_map(pieces.clone(), "0.0").is_err());
+    assert!(Board::from_map(pieces.clone(), "").is_err());
+}
+
+#[test]
+fn test_torus() {
+    assert_eq!(corner_board(4, 4).with_topology(Topology::CYLINDER).unwrap().symmetries().len(), 4);
+    assert_eq!(corner_board(4, 4).with_topology(Topology::TORUS).unwrap().symmetries().len(), 8);
+    assert_eq!(corner_board(4, 2).with_topology(Topology::TORUS).err(), Some(BlokusError::CannotWrap(2)));
+    let mut b = Board::new(::pieces::compute_pieces(3), 5, 5, &[(0, 0), (2, 2)]).unwrap()
+        .with_topology(Topology::TORUS).unwrap();
+    // A bar across the left and right edges, from the top right corner.
+    let bar = (0..b.piece_count()).find_map(|p| b.find_move(p, &[4, 0, 1])).unwrap();
+    assert_eq!(bar.2, 4);
+    assert_eq!(b.cells(bar), vec![4, 0, 1]);
+    b.place(0, bar).unwrap();
+    // Across the top and bottom edges, cell 20 touches the side of the bar
+    // and cell 23 only its corner.
+    assert!(!b.is_free(0, 20));
+    assert!(b.is_corner(0, 23));
+    assert_check_move_matches_legal(&b, 0);
+    for &mv in b.legal_moves(0).unwrap().iter() {
+        assert_eq!(b.find_move(mv.0, &b.cells(mv)), Some(mv));
     }
+    assert_eq!(b.with_topology(Topology::PLANE).err(), Some(BlokusError::BoardInPlay));
+}
+
+#[test]
+fn test_piece_sets() {
+    // Pieces 0 and 1 are the monomino and the domino, the others trominoes.
+    let pieces = ::pieces::compute_pieces(3);
+    let n = pieces.len();
+    let b = Board::new(pieces.clone(), 5, 5, &[(0, 0), (4, 4)]).unwrap();
+    assert_eq!(b.symmetries().len(), 4);
+    assert!(b.clone().with_piece_sets(vec![vec![1; n]]).is_err());
+    assert!(b.clone().with_piece_sets(vec![vec![1; n], vec![1; n - 1]]).is_err());
+    // Color 0 lacks the trominoes and color 1 has three monominoes.
+    let mut sets = vec![vec![1; n], vec![1; n]];
+    for copies in sets[0][2..].iter_mut() {
+        *copies = 0;
+    }
+    sets[1][0] = 3;
+    let mut b = b.with_piece_sets(sets.clone()).unwrap();
+    // The symmetries that swap the two colors are gone.
+    assert_eq!(b.symmetries().len(), 2);
+    assert_eq!((b.remaining_squares(0), b.remaining_squares(1)), (3, 3 * n - 1));
+    assert!(b.legal_moves(0).unwrap().iter().all(|&(p, _, _)| p < 2));
+    let mono = b.find_move(0, &[24]).unwrap();
+    assert_eq!(b.copies_left(1, 0), 3);
+    b.place(1, mono).unwrap();
+    for &i in &[18, 12] {
+        let mv = b.find_move(0, &[i]).unwrap();
+        b.place(1, mv).unwrap();
+    }
+    assert!(!b.has_piece(1, 0));
+    assert_eq!(b.try_place(1, mono.0, mono.1, 6), Err(BlokusError::PieceUsed(0)));
+    b.undo().unwrap();
+    assert_eq!(b.copies_left(1, 0), 1);
+    assert_eq!(b.remaining_squares(1), 3 * n - 3);
+    assert_eq!(b.with_piece_sets(sets).unwrap_err(), BlokusError::BoardInPlay);
+}
+
+#[test]
+fn test_start_rules() {
+    assert_eq!(StartRule::parse("cells:1,2;3,4"), Ok(StartRule::AnyOf(vec![(1, 2), (3, 4)])));
+    assert_eq!(StartRule::parse("edge"), Ok(StartRule::Edge));
+    assert!(StartRule::parse("cells:1").is_err());
+    assert!(StartRule::parse("corner").is_err());
+    let board = |rule| corner_board(4, 4).with_start_rule(rule);
+    assert_eq!(board(StartRule::AnyOf(vec![(0, 0)])).unwrap().symmetries().len(), 2);
+    assert_eq!(board(StartRule::Edge).unwrap().symmetries().len(), 8);
+    assert_eq!(board(StartRule::AnyOf(vec![(4, 0)])).unwrap_err(), BlokusError::BadStart((4, 0)));
+    assert_eq!(board(StartRule::AnyOf(vec![(0, -1)])).unwrap_err(), BlokusError::BadStart((0, -1)));
+    assert_eq!(board(StartRule::AnyOf(vec![])).unwrap_err(), BlokusError::NoStartCells);
+    let torus = corner_board(4, 4).with_topology(Topology::TORUS).unwrap();
+    assert_eq!(torus.with_start_rule(StartRule::Edge).unwrap_err(), BlokusError::NoStartCells);
+
+    let pieces = ::pieces::compute_pieces(3);
+    let b = Board::new(pieces, 5, 5, &[(0, 0), (4, 4)]).unwrap();
+    let mono = b.find_move(0, &[0]).unwrap();
+    let mut b = b.with_start_rule(StartRule::Anywhere).unwrap();
+    let all = b.legal_moves(0).unwrap();
+    assert!(all.contains(&(mono.0, mono.1, 12)));
+    b.place(0, (mono.0, mono.1, 12)).unwrap();
+    assert_eq!(b.check_move(0, 1, 0, 0), Err(BlokusError::NoCornerContact));
+    assert!(b.check_move(1, mono.0, mono.1, 0).is_ok());
+    b.undo().unwrap();
+    assert_eq!(b.legal_moves(0).unwrap(), all);
+
+    let mut b = b.with_start_rule(StartRule::Edge).unwrap();
+    assert_eq!(b.check_move(0, mono.0, mono.1, 12), Err(BlokusError::NoStartCell));
+    b.place(0, (mono.0, mono.1, 2)).unwrap();
+    assert_check_move_matches_legal(&b, 1);
+    b.undo().unwrap();
+
+    // Both colors start on the center, so only the first to move can.
+    let mut b = b.with_start_rule(StartRule::AnyOf(vec![(2, 2)])).unwrap();
+    assert!(b.legal_moves(0).unwrap().iter().all(|&mv| b.cells(mv).contains(&12)));
+    b.place(0, (mono.0, mono.1, 12)).unwrap();
+    assert!(b.legal_moves(1).unwrap().is_empty());
+}
+
+#[test]
+fn test_rules() {
+    let b = Board::new(::pieces::compute_pieces(3), 5, 5, &[(0, 0), (4, 0), (4, 4), (0, 4)]).unwrap();
+    let mono = b.find_move(0, &[0]).unwrap();
+    let check = |b: &Board, color, cells: &[usize]| {
+        let (p, v, anchor) = b.find_move(1, cells).unwrap();
+        b.check_move(color, p, v, anchor).map(|_| ())
+    };
+    assert_eq!(b.clone().with_rules(Rules { teams: vec![0, 1], ..Rules::default() }).unwrap_err(),
+               BlokusError::BadTeams { teams: 2, colors: 4 });
+
+    // Colors 0 and 2 against 1 and 3, with the classic rules otherwise.
+    let rules = Rules { teams: vec![0, 1, 0, 1], ..Rules::default() };
+    let mut t = b.clone().with_rules(rules).unwrap();
+    assert!(t.same_team(0, 2) && !t.same_team(0, 1));
+    assert_eq!(t.symmetries().len(), 8);
+    // Teams of neighboring corners cannot be rotated a quarter turn.
+    let rules = Rules { teams: vec![0, 0, 1, 1], ..Rules::default() };
+    assert_eq!(b.clone().with_rules(rules).unwrap().symmetries().len(), 4);
+    t.place(0, (mono.0, mono.1, 0)).unwrap();
+    // The first piece of a color still covers its home.
+    assert_eq!(check(&t, 2, &[6, 7]), Err(BlokusError::HomeNotCovered(24)));
+    t.place(2, (mono.0, mono.1, 24)).unwrap();
+    assert_eq!(check(&t, 2, &[6, 7]), Ok(()));
+    assert_eq!(check(&t, 2, &[1, 2]), Err(BlokusError::EdgeContact(1)));
+    assert_eq!(check(&t, 1, &[6, 7]), Err(BlokusError::HomeNotCovered(4)));
+    t.undo().unwrap();
+    assert_eq!(check(&t, 2, &[6, 7]), Err(BlokusError::HomeNotCovered(24)));
+
+    let rules = Rules { edge_contact: true, ..Rules::default() };
+    let mut e = b.clone().with_rules(rules).unwrap();
+    e.place(0, (mono.0, mono.1, 0)).unwrap();
+    assert_eq!(check(&e, 0, &[1, 2]), Ok(()));
+    assert_eq!(check(&e, 0, &[6, 7]), Ok(()));
+    assert_eq!(check(&e, 0, &[2, 3]), Err(BlokusError::NoCornerContact));
+    assert_eq!(e.clone().with_rules(Rules::default()).unwrap_err(), BlokusError::BoardInPlay);
+
+    let rules = Rules { any_corner: true, start: StartRule::Anywhere, ..Rules::default() };
+    let mut a = b.with_rules(rules).unwrap();
+    a.place(1, (mono.0, mono.1, 12)).unwrap();
+    a.place(0, (mono.0, mono.1, 6)).unwrap();
+    // Color 0 may continue from the corner of color 1 as well as its own.
+    assert_eq!(check(&a, 0, &[18, 19]), Ok(()));
+    assert_eq!(check(&a, 0, &[2, 3]), Ok(()));
+    assert_eq!(check(&a, 0, &[7, 8]), Err(BlokusError::EdgeContact(7)));
+    assert_eq!(check(&a, 0, &[3, 4]), Err(BlokusError::NoCornerContact));
+    assert_check_move_matches_legal(&a, 0);
 }
@@ -0,0 +1,190 @@
+//! Saving the tiling search of the `blokus` binary to resume it later.
+//!
+//! A checkpoint holds the search tree, the best value found with its solution,
+//! the positions printed so far, and the command-line options that describe the game and the schedule of the
+//! temperature, so that a resumed search plays the same game and continues
+//! the schedule from the number of iterations in the tree.
+//! The random number generator cannot be saved, so instead it is reseeded
+//! from itself with `reseed` whenever a checkpoint is taken; a search resumed
+//! from the seed in the checkpoint then continues exactly as the original would.
+//!
+//! Files are little-endian: the magic `BKCP` and the `u32` version (3), the `u32`
+//! number of options followed by each as a `u32` length and UTF-8 bytes, the `u64`
+//! seed, the `f64` best value, the best solution as a `u32` length and UTF-8 bytes,
+//! the `u32` number of printed positions followed by their `u64` canonical hashes,
+//! and finally the tree as in `Tree::write`.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use treesearch::Tree;
+
+const MAGIC: &[u8; 4] = b"BKCP";
+const VERSION: u32 = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    /// The options the search was started with.
+    pub args: Vec<String>,
+    pub seed: u64,
+    pub best: f64,
+    /// Description of the best position found so far.
+    pub best_solution: String,
+    /// Canonical hashes of the positions printed so far, which are not printed again.
+    pub printed: Vec<u64>,
+    pub tree: Tree,
+}
+
+/// Replace `rng` by a generator seeded from it, and return the seed.
+pub fn reseed(rng: &mut StdRng) -> u64 {
+    let seed = rng.gen();
+    *rng = StdRng::seed_from_u64(seed);
+    seed
+}
+
+fn write_string<W: Write>(w: &mut W, s: &str) -> io::Result<()> {
+    w.write_all(&(s.len() as u32).to_le_bytes())?;
+    w.write_all(s.as_bytes())
+}
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_string<R: Read>(r: &mut R) -> io::Result<String> {
+    let n = read_u32(r)? as usize;
+    let mut buf = Vec::new();
+    r.take(n as u64).read_to_end(&mut buf)?;
+    if buf.len() < n {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    String::from_utf8(buf).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid UTF-8 in checkpoint"))
+}
+
+impl Checkpoint {
+    pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&VERSION.to_le_bytes())?;
+        w.write_all(&(self.args.len() as u32).to_le_bytes())?;
+        for arg in self.args.iter() {
+            write_string(w, arg)?;
+        }
+        w.write_all(&self.seed.to_le_bytes())?;
+        w.write_all(&self.best.to_le_bytes())?;
+        write_string(w, &self.best_solution)?;
+        w.write_all(&(self.printed.len() as u32).to_le_bytes())?;
+        for hash in self.printed.iter() {
+            w.write_all(&hash.to_le_bytes())?;
+        }
+        self.tree.write(w)
+    }
+
+    pub fn read<R: Read>(r: &mut R) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a checkpoint"));
+        }
+        if read_u32(r)? != VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "unsupported checkpoint version"));
+        }
+        let mut args = Vec::new();
+        for _ in 0..read_u32(r)? {
+            args.push(read_string(r)?);
+        }
+        let seed = read_u64(r)?;
+        let best = f64::from_bits(read_u64(r)?);
+        let best_solution = read_string(r)?;
+        let mut printed = Vec::new();
+        for _ in 0..read_u32(r)? {
+            printed.push(read_u64(r)?);
+        }
+        let tree = Tree::read(r)?;
+        Ok(Checkpoint { args, seed, best, best_solution, printed, tree })
+    }
+
+    /// Write the checkpoint to `path` through a temporary file, so that an
+    /// interrupted save leaves the previous checkpoint intact.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        {
+            let mut w = BufWriter::new(File::create(&tmp)?);
+            self.write(&mut w)?;
+            w.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        }
+        fs::rename(&tmp, path)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read(&mut BufReader::new(File::open(path)?))
+    }
+}
+
+#[test]
+fn test_resume_continues_the_search() {
+    use std::time::Duration;
+    use game::Game;
+    use schedule::Linear;
+    use telemetry::Telemetry;
+    use treesearch::{Uniform, resume_treesearch};
+    let schedule = Linear { start: 0.01, rate: 0.01 };
+    let new_game = || Game::standard(7, 2, 3).unwrap();
+    let telemetry = || Telemetry::new(Duration::from_secs(1));
+    // Stop after 30 iterations, taking a checkpoint, and go on for 30 more.
+    let mut rng = StdRng::seed_from_u64(5);
+    let mut iterations = 0;
+    let mut checkpoint = None;
+    let mut game = new_game();
+    let tree = resume_treesearch(&mut game, Uniform, None, &mut rng, &schedule, Tree::new(), &mut telemetry(), |_, tree, rng| {
+        iterations += 1;
+        if iterations <= 30 {
+            return true;
+        }
+        let seed = reseed(rng);
+        checkpoint = Some(Checkpoint {
+            args: vec!["--threshold".to_string(), "2".to_string()],
+            seed,
+            best: 1.5,
+            best_solution: "solution".to_string(),
+            printed: vec![3, 1 << 40],
+            tree: tree.clone(),
+        });
+        false
+    });
+    assert_eq!(tree.visits(), 30);
+    let mut count = 0;
+    let original = resume_treesearch(&mut game, Uniform, None, &mut rng, &schedule, tree, &mut telemetry(), |_, _, _| {
+        count += 1;
+        count <= 30
+    });
+
+    let mut bytes = Vec::new();
+    checkpoint.unwrap().write(&mut bytes).unwrap();
+    let checkpoint = Checkpoint::read(&mut &bytes[..]).unwrap();
+    assert_eq!(checkpoint.args, vec!["--threshold", "2"]);
+    assert_eq!(checkpoint.best_solution, "solution");
+    assert_eq!(checkpoint.printed, vec![3, 1 << 40]);
+    assert!(Checkpoint::read(&mut &bytes[..bytes.len() - 1]).is_err());
+    let mut rng = StdRng::seed_from_u64(checkpoint.seed);
+    let mut count = 0;
+    let resumed = resume_treesearch(&mut new_game(), Uniform, None, &mut rng, &schedule, checkpoint.tree, &mut telemetry(), |_, _, _| {
+        count += 1;
+        count <= 30
+    });
+    assert_eq!(resumed.visits(), 60);
+    assert_eq!(resumed, original);
+}
@@ -0,0 +1,220 @@
+//! Fixed-size encodings of moves and positions for neural networks.
+//!
+//! An action is `(piece * ORIENTATIONS + variation) * cells + anchor`, where `anchor`
+//! is the cell at the top left corner of the bounding box of the placed piece,
+//! followed by a single action for passing. Most actions never occur,
+//! because pieces have fewer than eight distinct orientations and do not fit
+//! at every anchor; `Perspective::legal_mask` tells which ones are legal.
+//!
+//! Both actions and observations are seen from the perspective of one color:
+//! the board is turned so that the home of that color is where the home of
+//! color 0 is, and the colors are listed starting with that color.
+
+use board::{Board, Color, Move, Symmetry};
+
+/// Upper bound on the number of distinct orientations of a piece.
+pub const ORIENTATIONS: usize = 8;
+
+/// Number of actions, including the pass action.
+pub fn action_count(board: &Board) -> usize {
+    board.piece_count() * ORIENTATIONS * board.size() + 1
+}
+
+pub fn pass_action(board: &Board) -> usize {
+    action_count(board) - 1
+}
+
+/// Dimensions `(planes, rows, cols)` of `Perspective::observation`: for each color
+/// its pieces, its corner cells and its blocked cells, then for each color and piece
+/// a plane of ones if the piece is still in hand.
+pub fn observation_shape(board: &Board) -> (usize, usize, usize) {
+    let colors = board.colors();
+    (colors * (3 + board.piece_count()), board.rows(), board.cols())
+}
+
+/// A view of the board from one color.
+#[derive(Debug, Clone)]
+pub struct Perspective {
+    color: Color,
+    colors: usize,
+    /// `cells[i]` is where cell `i` of the board appears in the view.
+    cells: Vec<usize>,
+    /// `board_cells[j]` is the cell of the board shown at `j` in the view.
+    board_cells: Vec<usize>,
+}
+
+impl Perspective {
+    /// The view for `color`. If `rotate` is set, the board is turned by the first
+    /// of its symmetries that takes the home of the `k`th color after `color` to
+    /// the home of color `k`, in the order the colors are listed, if there is one;
+    /// otherwise it is shown as is.
+    pub fn new(board: &Board, color: Color, rotate: bool) -> Self {
+        let colors = board.colors();
+        let cyclic = |s: &&Symmetry| (0..colors).all(|k| s.color(((color as usize + k) % colors) as Color) == k as Color);
+        let symmetry = board.symmetries().iter().filter(|_| rotate).find(cyclic);
+        let cells = match symmetry {
+            Some(s) => (0..board.size()).map(|i| s.cell(i)).collect::<Vec<_>>(),
+            None => (0..board.size()).collect(),
+        };
+        let mut board_cells = vec![0; cells.len()];
+        for (i, &j) in cells.iter().enumerate() {
+            board_cells[j] = i;
+        }
+        Perspective { color, colors: board.colors(), cells, board_cells }
+    }
+
+    pub fn color(&self) -> Color {
+        self.color
+    }
+
+    /// The color listed `k`th in the view.
+    fn relative(&self, k: usize) -> Color {
+        ((self.color as usize + k) % self.colors) as Color
+    }
+
+    fn map_move(board: &Board, mv: Move, map: &[usize]) -> Move {
+        let cells = board.cells(mv).iter().map(|&i| map[i]).collect::<Vec<_>>();
+        board.find_move(mv.0, &cells).expect("symmetries map pieces onto pieces")
+    }
+
+    /// The action of a move, or of passing.
+    pub fn encode(&self, board: &Board, mv: Option<Move>) -> usize {
+        match mv {
+            None => pass_action(board),
+            Some(mv) => {
+                let (piece, variation, anchor) = Self::map_move(board, mv, &self.cells);
+                (piece * ORIENTATIONS + variation) * board.size() + anchor
+            },
+        }
+    }
+
+    /// The move of an action, which is on the board but not necessarily legal.
+    pub fn decode(&self, board: &Board, action: usize) -> Result<Option<Move>, String> {
+        if action == pass_action(board) {
+            return Ok(None);
+        }
+        if action > pass_action(board) {
+            return Err(format!("action {} out of range", action));
+        }
+        let (anchor, rest) = (action % board.size(), action / board.size());
+        let (piece, variation) = (rest / ORIENTATIONS, rest % ORIENTATIONS);
+        match board.move_at(piece, variation, anchor) {
+            Some(mv) => Ok(Some(Self::map_move(board, mv, &self.board_cells))),
+            None => Err(format!("action {} is not a placement on the board", action)),
+        }
+    }
+
+    /// Which actions are legal for the color of the view; passing is legal
+    /// exactly when nothing else is.
+    pub fn legal_mask(&self, board: &Board) -> Vec<bool> {
+        let mut mask = vec![false; action_count(board)];
+        let moves = board.legal_moves(self.color).unwrap();
+        for &mv in moves.iter() {
+            mask[self.encode(board, Some(mv))] = true;
+        }
+        if moves.is_empty() {
+            mask[pass_action(board)] = true;
+        }
+        mask
+    }
+
+    /// The planes described by `observation_shape`, flattened in row-major order.
+    pub fn observation(&self, board: &Board) -> Vec<f32> {
+        let size = board.size();
+        let (planes, _, _) = observation_shape(board);
+        let mut res = vec![0.0; planes * size];
+        for k in 0..self.colors {
+            let color = self.relative(k);
+            for i in 0..size {
+                let j = self.cells[i];
+                if board.at(i) == Some(color) {
+                    res[k * size + j] = 1.0;
+                }
+                if board.is_corner(color, i) {
+                    res[(self.colors + k) * size + j] = 1.0;
+                }
+                if board.at(i).is_none() && !board.is_free(color, i) {
+                    res[(2 * self.colors + k) * size + j] = 1.0;
+                }
+            }
+            for piece in 0..board.piece_count() {
+                if board.has_piece(color, piece) {
+                    let plane = 3 * self.colors + k * board.piece_count() + piece;
+                    for x in &mut res[plane * size..(plane + 1) * size] {
+                        *x = 1.0;
+                    }
+                }
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+fn corner_board(size: usize, colors: usize) -> Board {
+    ::game::Game::standard(size, colors, 4).unwrap().board().clone()
+}
+
+#[test]
+fn test_encode_decode() {
+    let mut board = corner_board(7, 4);
+    for c in 0..4 {
+        let mv = board.legal_moves(c).unwrap()[3];
+        board.place(c, mv).unwrap();
+    }
+    for &rotate in &[false, true] {
+        for c in 0..4 {
+            let view = Perspective::new(&board, c, rotate);
+            let moves = board.legal_moves(c).unwrap();
+            let mask = view.legal_mask(&board);
+            assert_eq!(mask.iter().filter(|&&m| m).count(), moves.len());
+            for &mv in moves.iter() {
+                let action = view.encode(&board, Some(mv));
+                assert!(mask[action]);
+                assert_eq!(view.decode(&board, action), Ok(Some(mv)));
+            }
+        }
+    }
+    let view = Perspective::new(&board, 0, true);
+    assert_eq!(view.decode(&board, pass_action(&board)), Ok(None));
+    assert!(view.decode(&board, pass_action(&board) + 1).is_err());
+    // A domino anchored at the last cell, and a monomino in a missing orientation.
+    assert!(view.decode(&board, ORIENTATIONS * 49 + 48).is_err());
+    assert!(view.decode(&board, 7 * 49).is_err());
+}
+
+#[test]
+fn test_perspective() {
+    let mut board = corner_board(6, 2);
+    let (planes, rows, cols) = observation_shape(&board);
+    assert_eq!((planes, rows, cols), (2 * (3 + 9), 6, 6));
+    let first = Perspective::new(&board, 0, true);
+    let second = Perspective::new(&board, 1, true);
+    assert_eq!(first.observation(&board), second.observation(&board));
+    assert_eq!(first.legal_mask(&board), second.legal_mask(&board));
+    assert_ne!(Perspective::new(&board, 1, false).observation(&board), first.observation(&board));
+
+    // Color 1 answers with the same move turned around; then both
+    // colors see the same position from their own side.
+    let mv = board.legal_moves(0).unwrap()[5];
+    board.place(0, mv).unwrap();
+    let answer = second.decode(&board, first.encode(&board, Some(mv))).unwrap().unwrap();
+    board.place(1, answer).unwrap();
+    let first = Perspective::new(&board, 0, true);
+    let second = Perspective::new(&board, 1, true);
+    assert_eq!(first.observation(&board), second.observation(&board));
+    assert_eq!(first.observation(&board)[..36].iter().sum::<f32>(), board.piece_size(mv.0) as f32);
+}
+
+#[test]
+fn test_perspective_keeps_color_order() {
+    // With homes in three corners, the only symmetry besides the identity is the
+    // reflection swapping colors 0 and 2, which would list color 1 before color 0
+    // in the view of color 2.
+    let board = corner_board(6, 3);
+    assert_eq!(board.symmetries().len(), 2);
+    for c in 0..3 {
+        let rotated = Perspective::new(&board, c, true);
+        assert_eq!(rotated.observation(&board), Perspective::new(&board, c, false).observation(&board));
+    }
+}
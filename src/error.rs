@@ -0,0 +1,101 @@
+//! Errors of the board and game API.
+
+use std::error::Error;
+use std::fmt;
+
+use board::{Color, Move};
+use game::{STANDARD_COLORS, STANDARD_MAX_PIECE_SIZE, STANDARD_MAX_SIZE};
+use pieces::Point;
+
+/// Why a board could not be created or a move played or taken back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlokusError {
+    /// A color without a home on the board.
+    InvalidColor(Color),
+    NoSuchPiece(usize),
+    /// A variation of a piece beyond its list of orientations.
+    NoSuchOrientation { piece: usize, variation: usize },
+    /// A move that does not stay on the board.
+    OutOfBounds(Move),
+    /// A move with a piece the color has no copy of left.
+    PieceUsed(usize),
+    /// A move covering the given cell, which is a hole or a neutral cell.
+    ClosedCell(usize),
+    /// A move covering the given cell, which is not empty.
+    OccupiedCell(usize),
+    /// A move covering the given cell, which is next to a piece of the same color.
+    EdgeContact(usize),
+    /// A first move that does not cover the given home cell.
+    HomeNotCovered(usize),
+    /// A first move that covers no start cell, when the start rule is not `StartRule::Home`.
+    NoStartCell,
+    /// A move that does not touch the corner of a piece of the same color.
+    NoCornerContact,
+    /// A move index beyond the list of moves.
+    NoSuchMove { index: usize, count: usize },
+    /// A pass while the color to move has a legal move.
+    IllegalPass,
+    NothingToUndo,
+    /// A home outside the board, on a cell that is not open, or shared with another color.
+    BadHome(Point),
+    TooManyColors { colors: usize, max: usize },
+    /// The board has fewer cells than needed.
+    BoardTooSmall { cells: usize, needed: usize },
+    /// An ASCII map of a board that cannot be read, and why.
+    BadMap(String),
+    /// A start cell, as `(x, y)`, that is not an open cell of the board.
+    BadStart(Point),
+    /// A start rule that leaves no cell for the first piece of a color.
+    NoStartCells,
+    /// Teams given for a number of colors other than that of the board.
+    BadTeams { teams: usize, colors: usize },
+    /// An axis of the given length, too short to wrap around.
+    CannotWrap(usize),
+    /// A change to the setup of a board that already has pieces on it.
+    BoardInPlay,
+    /// Piece sets that do not match the colors and pieces of the board, and why.
+    BadPieceSets(String),
+    /// A size, number of colors or piece size that `Game::standard` does not offer.
+    UnsupportedGame { size: usize, colors: usize, piece_size: usize },
+}
+
+impl fmt::Display for BlokusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BlokusError::InvalidColor(color) => write!(f, "invalid color {}", color),
+            BlokusError::NoSuchPiece(piece) => write!(f, "there is no piece {}", piece),
+            BlokusError::NoSuchOrientation { piece, variation } =>
+                write!(f, "piece {} has no orientation {}", piece, variation),
+            BlokusError::OutOfBounds((piece, variation, anchor)) =>
+                write!(f, "piece {} in orientation {} at cell {} leaves the board", piece, variation, anchor),
+            BlokusError::PieceUsed(piece) => write!(f, "no copy of piece {} is left to place", piece),
+            BlokusError::ClosedCell(i) => write!(f, "cell {} is not open", i),
+            BlokusError::OccupiedCell(i) => write!(f, "cell {} is occupied", i),
+            BlokusError::EdgeContact(i) => write!(f, "cell {} is next to a piece of the same color", i),
+            BlokusError::HomeNotCovered(i) => write!(f, "the first piece must cover the home cell {}", i),
+            BlokusError::NoStartCell => write!(f, "the first piece must cover a start cell"),
+            BlokusError::NoCornerContact => write!(f, "the piece does not touch a corner of a piece of the same color"),
+            BlokusError::NoSuchMove { index, count } => write!(f, "no move {} among {} moves", index, count),
+            BlokusError::IllegalPass => write!(f, "cannot pass while a move is available"),
+            BlokusError::NothingToUndo => write!(f, "no moves to undo"),
+            BlokusError::BadHome((x, y)) => write!(f, "home ({}, {}) is not an open cell of its own on the board", x, y),
+            BlokusError::TooManyColors { colors, max } => write!(f, "{} colors but at most {} are supported", colors, max),
+            BlokusError::BoardTooSmall { cells, needed } =>
+                write!(f, "the board has {} cells but {} are needed", cells, needed),
+            BlokusError::BadMap(ref message) => write!(f, "invalid map: {}", message),
+            BlokusError::BadStart((x, y)) => write!(f, "start cell ({}, {}) is not an open cell of the board", x, y),
+            BlokusError::NoStartCells => write!(f, "the start rule leaves no cell to start from"),
+            BlokusError::BadTeams { teams, colors } =>
+                write!(f, "teams are given for {} colors but the board has {}", teams, colors),
+            BlokusError::CannotWrap(n) => write!(f, "an axis of {} cells is too short to wrap around", n),
+            BlokusError::BoardInPlay => write!(f, "the setup of a board cannot change once pieces are placed"),
+            BlokusError::BadPieceSets(ref message) => write!(f, "invalid piece sets: {}", message),
+            BlokusError::UnsupportedGame { size, colors, piece_size } =>
+                write!(f, "no standard game of size {} with {} colors and pieces of up to {} squares; \
+                           expected a size of 2 to {}, 2 to {} colors and pieces of 1 to {} squares",
+                       size, colors, piece_size, STANDARD_MAX_SIZE, STANDARD_COLORS, STANDARD_MAX_PIECE_SIZE),
+        }
+    }
+}
+
+impl Error for BlokusError {}
@@ -0,0 +1,240 @@
+use board::{Board, Color, Move};
+
+/// A number describing the prospects of one color in a position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    /// Free cells where the color may start its next piece.
+    AvailableCorners,
+    /// Free cells reachable from an available corner through free cells,
+    /// i.e. the area the color could still cover given enough pieces.
+    ReachableArea,
+    /// Squares in the pieces the color has not placed yet.
+    RemainingSquares,
+    /// Number of legal moves.
+    Mobility,
+    /// Cells that another team occupies but that would otherwise be corners of the
+    /// color: diagonal to a piece of its team and, unless side contact is allowed,
+    /// not next to one.
+    BlockedCorners,
+    /// Empty cells the color may no longer use because they are next to a piece
+    /// of its team.
+    Frontier,
+}
+
+pub const FEATURES: [Feature; 6] = [
+    Feature::AvailableCorners,
+    Feature::ReachableArea,
+    Feature::RemainingSquares,
+    Feature::Mobility,
+    Feature::BlockedCorners,
+    Feature::Frontier,
+];
+
+impl Feature {
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::AvailableCorners => "corners",
+            Feature::ReachableArea => "reachable",
+            Feature::RemainingSquares => "remaining",
+            Feature::Mobility => "mobility",
+            Feature::BlockedCorners => "blocked",
+            Feature::Frontier => "frontier",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Feature> {
+        FEATURES.iter().cloned().find(|f| f.name() == name)
+    }
+
+    pub fn compute(self, board: &Board, color: Color) -> f64 {
+        (match self {
+            Feature::AvailableCorners => available_corners(board, color),
+            Feature::ReachableArea => reachable_area(board, color),
+            Feature::RemainingSquares => board.remaining_squares(color),
+            Feature::Mobility => board.legal_moves(color).unwrap().len(),
+            Feature::BlockedCorners => blocked_corners(board, color),
+            Feature::Frontier => frontier(board, color),
+        }) as f64
+    }
+}
+
+pub fn available_corners(board: &Board, color: Color) -> usize {
+    (0..board.size()).filter(|&i| board.is_corner(color, i)).count()
+}
+
+pub fn reachable_area(board: &Board, color: Color) -> usize {
+    let mut visited = vec![false; board.size()];
+    let mut stack = (0..board.size()).filter(|&i| board.is_corner(color, i)).collect::<Vec<_>>();
+    let mut count = 0;
+    while let Some(i) = stack.pop() {
+        if visited[i] {
+            continue;
+        }
+        visited[i] = true;
+        count += 1;
+        for j in board.cardinal_neighbors(i) {
+            if !visited[j] && board.is_free(color, j) {
+                stack.push(j);
+            }
+        }
+    }
+    count
+}
+
+pub fn blocked_corners(board: &Board, color: Color) -> usize {
+    let ours = |j: usize| board.at(j).map_or(false, |d| board.same_team(color, d));
+    let edge_contact = board.rules().edge_contact;
+    (0..board.size()).filter(|&i| match board.at(i) {
+        Some(c) if !board.same_team(color, c) => {
+            board.diagonal_neighbors(i).any(&ours) && (edge_contact || !board.cardinal_neighbors(i).any(&ours))
+        },
+        _ => false,
+    }).count()
+}
+
+pub fn frontier(board: &Board, color: Color) -> usize {
+    (0..board.size()).filter(|&i| board.is_vacant(i) && !board.is_free(color, i)).count()
+}
+
+/// A linear combination of features.
+#[derive(Debug, Clone)]
+pub struct WeightedEvaluator {
+    pub weights: Vec<(Feature, f64)>,
+    /// If set, the average score of the other colors is subtracted,
+    /// so that the evaluation measures the advantage of the color.
+    pub relative: bool,
+}
+
+impl Default for WeightedEvaluator {
+    fn default() -> Self {
+        WeightedEvaluator {
+            weights: vec![
+                (Feature::RemainingSquares, -1.0),
+                (Feature::AvailableCorners, 1.0),
+                (Feature::ReachableArea, 0.1),
+                (Feature::BlockedCorners, -0.5),
+            ],
+            relative: true,
+        }
+    }
+}
+
+impl WeightedEvaluator {
+    /// Parse a comma-separated list of `feature=weight`, e.g. `remaining=-1,corners=2`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut weights = Vec::new();
+        for term in spec.split(',') {
+            let mut parts = term.splitn(2, '=');
+            let name = parts.next().unwrap().trim();
+            let feature = Feature::from_name(name).ok_or_else(|| format!("unknown feature {:?}", name))?;
+            let weight = match parts.next().map(|w| w.trim().parse::<f64>()) {
+                Some(Ok(w)) => w,
+                _ => return Err(format!("expected {}=WEIGHT", name)),
+            };
+            weights.push((feature, weight));
+        }
+        Ok(WeightedEvaluator { weights, relative: true })
+    }
+
+    fn absolute(&self, board: &Board, color: Color) -> f64 {
+        self.weights.iter().map(|&(f, w)| w * f.compute(board, color)).sum()
+    }
+
+    /// Scores of all colors.
+    pub fn evaluate_all(&self, board: &Board) -> Vec<f64> {
+        let scores = (0..board.colors()).map(|c| self.absolute(board, c as Color)).collect::<Vec<_>>();
+        if !self.relative || scores.len() < 2 {
+            return scores;
+        }
+        let total = scores.iter().sum::<f64>();
+        let others = (scores.len() - 1) as f64;
+        scores.iter().map(|&s| s - (total - s) / others).collect()
+    }
+
+    pub fn evaluate(&self, board: &Board, color: Color) -> f64 {
+        if !self.relative || board.colors() < 2 {
+            return self.absolute(board, color);
+        }
+        self.evaluate_all(board)[color as usize]
+    }
+}
+
+/// The move for `color` whose resulting position `evaluator` likes best,
+/// or `None` if `color` cannot move.
+pub fn greedy_move(board: &mut Board, color: Color, evaluator: &WeightedEvaluator) -> Option<Move> {
+    let mut best = None;
+    let mut best_score = f64::NEG_INFINITY;
+    for mv in board.legal_moves(color).unwrap() {
+        board.place(color, mv).unwrap();
+        let score = evaluator.evaluate(board, color);
+        board.undo().unwrap();
+        if score > best_score {
+            best_score = score;
+            best = Some(mv);
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+fn test_board() -> Board {
+    Board::new(::pieces::compute_pieces(3), 6, 6, &[(0, 0), (5, 5)]).unwrap()
+}
+
+#[test]
+fn test_features_on_empty_board() {
+    let b = test_board();
+    assert_eq!(Feature::AvailableCorners.compute(&b, 0), 1.0);
+    assert_eq!(Feature::ReachableArea.compute(&b, 0), 36.0);
+    assert_eq!(Feature::RemainingSquares.compute(&b, 0), 9.0);
+    assert_eq!(Feature::Mobility.compute(&b, 0), 8.0);
+    assert_eq!(Feature::BlockedCorners.compute(&b, 0), 0.0);
+    assert_eq!(Feature::Frontier.compute(&b, 0), 0.0);
+}
+
+#[test]
+fn test_features_after_move() {
+    let mut b = test_board();
+    // Vertical I-tromino in the corner of color 0.
+    let mv = b.legal_moves(0).unwrap().into_iter().find(|&(p, _, _)| b.piece_size(p) == 3).unwrap();
+    b.place(0, mv).unwrap();
+    assert_eq!((b.at(0), b.at(6), b.at(12)), (Some(0), Some(0), Some(0)));
+    assert_eq!(Feature::RemainingSquares.compute(&b, 0), 6.0);
+    assert_eq!(Feature::AvailableCorners.compute(&b, 0), 1.0);
+    assert_eq!(Feature::Frontier.compute(&b, 0), 4.0);
+    assert_eq!(Feature::ReachableArea.compute(&b, 0), 36.0 - 3.0 - 4.0);
+    assert_eq!(Feature::RemainingSquares.compute(&b, 1), 9.0);
+}
+
+#[test]
+fn test_features_with_teams() {
+    use board::{Rules, StartRule};
+    let b = Board::new(::pieces::compute_pieces(1), 5, 5, &[(0, 0), (4, 0), (4, 4), (0, 4)]).unwrap();
+    let mono = b.find_move(0, &[0]).unwrap();
+    let rules = Rules { teams: vec![0, 1, 0, 1], start: StartRule::Anywhere, ..Rules::default() };
+    let mut t = b.clone().with_rules(rules.clone()).unwrap();
+    // Color 2 in the center and color 1 at its corner.
+    t.place(2, (mono.0, mono.1, 12)).unwrap();
+    t.place(1, (mono.0, mono.1, 6)).unwrap();
+    // The pieces of color 2 count for its teammate 0.
+    assert_eq!(frontier(&t, 0), 4);
+    assert_eq!(blocked_corners(&t, 0), 1);
+    assert_eq!(blocked_corners(&t, 2), 1);
+    assert_eq!(blocked_corners(&t, 1), 1);
+    assert_eq!(frontier(&t, 3), 4);
+
+    // With side contact allowed, no cell is lost to the frontier.
+    let mut e = b.with_rules(Rules { edge_contact: true, ..rules }).unwrap();
+    e.place(2, (mono.0, mono.1, 12)).unwrap();
+    assert_eq!(frontier(&e, 0), 0);
+}
+
+#[test]
+fn test_greedy_prefers_big_pieces() {
+    let mut b = test_board();
+    let evaluator = WeightedEvaluator::parse("remaining=-1").unwrap();
+    let (piece, _, _) = greedy_move(&mut b, 0, &evaluator).unwrap();
+    assert_eq!(b.piece_size(piece), 3);
+    assert!(WeightedEvaluator::parse("remaining").is_err());
+    assert!(WeightedEvaluator::parse("nonsense=1").is_err());
+}
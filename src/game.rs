@@ -0,0 +1,263 @@
+use board::{Board, Color, Move};
+use error::BlokusError;
+use eval::WeightedEvaluator;
+use pieces::compute_pieces;
+use treesearch::GameStateMut;
+
+/// Largest number of colors of `Game::standard`.
+pub const STANDARD_COLORS: usize = 8;
+/// Largest board size of `Game::standard`.
+pub const STANDARD_MAX_SIZE: usize = 100;
+/// Largest piece size of `Game::standard`.
+pub const STANDARD_MAX_PIECE_SIZE: usize = 6;
+
+/// Bonus for placing every piece.
+pub const ALL_PLACED_BONUS: i32 = 15;
+/// Extra bonus if the last piece placed was the monomino.
+pub const MONOMINO_LAST_BONUS: i32 = 5;
+
+/// Standard Blokus score of `color`: minus the squares left in hand,
+/// plus the bonuses for placing all pieces.
+pub fn score(board: &Board, color: Color) -> i32 {
+    let remaining = board.remaining_squares(color);
+    if remaining > 0 {
+        return -(remaining as i32);
+    }
+    let last = board.history().iter().rev().find(|&&(c, _, _, _)| c == color);
+    match last {
+        Some(&(_, piece, _, _)) if board.piece_size(piece) == 1 => ALL_PLACED_BONUS + MONOMINO_LAST_BONUS,
+        _ => ALL_PLACED_BONUS,
+    }
+}
+
+/// Result of a game for each color: 1 if it scored more than every other color,
+/// -1 if another color scored more, and 0 if it shares the lead.
+pub fn outcomes(scores: &[i32]) -> Vec<f64> {
+    (0..scores.len()).map(|c| {
+        let best_other = scores.iter().enumerate().filter(|&(d, _)| d != c).map(|(_, &s)| s).max();
+        best_other.map_or(0.0, |b| (scores[c] - b).signum() as f64)
+    }).collect()
+}
+
+/// A competitive game: colors move in turn, a color that cannot place a piece passes,
+/// and the game ends when nobody can move.
+#[derive(Clone)]
+pub struct Game {
+    board: Board,
+    /// Moves played so far; `None` is a pass.
+    history: Vec<Option<Move>>,
+    /// Legal moves of the color to move, if computed since the last change.
+    legal: Option<Vec<Move>>,
+    /// Used for `MultiPlayerState::evaluate` at the search horizon.
+    pub evaluator: WeightedEvaluator,
+    /// The color `GameStateMut::value` is computed for.
+    pub perspective: Color,
+}
+
+impl Game {
+    pub fn new(board: Board) -> Self {
+        Game {
+            board,
+            history: Vec::new(),
+            legal: None,
+            evaluator: WeightedEvaluator::default(),
+            perspective: 0,
+        }
+    }
+
+    /// A `size` by `size` board with all pieces of up to `piece_size` squares,
+    /// where up to 4 colors start in the corners, clockwise from the top left,
+    /// and up to 4 more in the middle of the sides; two colors start in opposite corners.
+    pub fn standard(size: usize, colors: usize, piece_size: usize) -> Result<Self, BlokusError> {
+        if colors > STANDARD_COLORS {
+            return Err(BlokusError::TooManyColors { colors, max: STANDARD_COLORS });
+        }
+        if !(2..=STANDARD_MAX_SIZE).contains(&size) || colors < 2 || !(1..=STANDARD_MAX_PIECE_SIZE).contains(&piece_size) {
+            return Err(BlokusError::UnsupportedGame { size, colors, piece_size });
+        }
+        let (n, m) = (size as i8 - 1, (size as i8 - 1) / 2);
+        let starts = [(0, 0), (n, 0), (n, n), (0, n), (m, 0), (n, m), (m, n), (0, m)];
+        let homes = if colors == 2 { vec![starts[0], starts[2]] } else { starts[..colors].to_vec() };
+        Ok(Game::new(Board::new(compute_pieces(piece_size), size, size, &homes)?))
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn history(&self) -> &[Option<Move>] {
+        &self.history
+    }
+
+    pub fn to_move(&self) -> Color {
+        (self.history.len() % self.board.colors()) as Color
+    }
+
+    /// Legal piece placements of the color to move.
+    pub fn legal_moves(&mut self) -> &[Move] {
+        if self.legal.is_none() {
+            let color = self.to_move();
+            self.legal = Some(self.board.legal_moves(color).unwrap());
+        }
+        self.legal.as_ref().unwrap()
+    }
+
+    /// Whether the color to move has to pass.
+    pub fn must_pass(&mut self) -> bool {
+        self.legal_moves().is_empty()
+    }
+
+    pub fn is_over(&mut self) -> bool {
+        if !self.must_pass() {
+            return false;
+        }
+        let color = self.to_move();
+        (0..self.board.colors() as Color)
+            .filter(|&c| c != color)
+            .all(|c| self.board.legal_moves(c).unwrap().is_empty())
+    }
+
+    /// Play a legal move, or pass with `None` when there is none.
+    pub fn play(&mut self, mv: Option<Move>) -> Result<(), BlokusError> {
+        let color = self.to_move();
+        match mv {
+            Some(mv) => self.board.place(color, mv)?,
+            None if !self.must_pass() => return Err(BlokusError::IllegalPass),
+            None => {},
+        }
+        self.history.push(mv);
+        self.legal = None;
+        Ok(())
+    }
+
+    pub fn take_back(&mut self) -> Option<Option<Move>> {
+        let mv = self.history.pop()?;
+        if mv.is_some() {
+            self.board.undo().unwrap();
+        }
+        self.legal = None;
+        Some(mv)
+    }
+
+    pub fn scores(&self) -> Vec<i32> {
+        (0..self.board.colors()).map(|c| score(&self.board, c as Color)).collect()
+    }
+
+    /// The move selected by `select_move(i)`.
+    pub fn move_at(&mut self, i: usize) -> Option<Move> {
+        if self.must_pass() {
+            assert_eq!(i, 0);
+            None
+        } else {
+            Some(self.legal_moves()[i])
+        }
+    }
+}
+
+impl GameStateMut for Game {
+    fn undo(&mut self) {
+        self.take_back().unwrap();
+    }
+
+    fn move_count(&mut self) -> usize {
+        if self.is_over() {
+            0
+        } else if self.must_pass() {
+            1
+        } else {
+            self.legal_moves().len()
+        }
+    }
+
+    fn select_move(&mut self, i: usize) {
+        let mv = self.move_at(i);
+        self.play(mv).expect("move index out of range");
+    }
+
+    /// Margin of `perspective` over the best other color.
+    fn value(&mut self) -> f64 {
+        let scores = self.scores();
+        let own = scores[self.perspective as usize];
+        let best_other = scores.iter().enumerate()
+            .filter(|&(c, _)| c != self.perspective as usize)
+            .map(|(_, &s)| s)
+            .max()
+            .unwrap_or(0);
+        (own - best_other) as f64
+    }
+
+    fn heuristic(&mut self) -> f64 {
+        self.evaluator.evaluate(&self.board, self.perspective)
+    }
+}
+
+#[cfg(test)]
+fn small_game() -> Game {
+    Game::new(Board::new(::pieces::compute_pieces(2), 4, 4, &[(0, 0), (3, 3)]).unwrap())
+}
+
+#[test]
+fn test_play_until_over() {
+    let mut g = small_game();
+    let mut passes = 0;
+    while g.move_count() > 0 {
+        if g.must_pass() {
+            passes += 1;
+        }
+        g.select_move(0);
+    }
+    // Both colors place both pieces: no passes are needed before the game ends.
+    assert_eq!(passes, 0);
+    assert_eq!(g.scores(), vec![ALL_PLACED_BONUS, ALL_PLACED_BONUS]);
+    while g.take_back().is_some() {}
+    assert_eq!(g.scores(), vec![-3, -3]);
+}
+
+#[test]
+fn test_monomino_last_bonus() {
+    let mut g = small_game();
+    // Domino first, then the monomino.
+    for _ in 0..4 {
+        let i = {
+            let legal = g.legal_moves().to_vec();
+            let want = if g.history().len() < 2 { 2 } else { 1 };
+            legal.iter().position(|&(p, _, _)| g.board().piece_size(p) == want).unwrap()
+        };
+        g.select_move(i);
+    }
+    assert_eq!(g.scores(), vec![ALL_PLACED_BONUS + MONOMINO_LAST_BONUS; 2]);
+}
+
+#[test]
+fn test_pass() {
+    // On a 1x3 board, color 0 at the left end and color 1 at the right.
+    let mut g = Game::new(Board::new(::pieces::compute_pieces(2), 1, 3, &[(0, 0), (2, 0)]).unwrap());
+    let domino = g.legal_moves().iter().position(|&(p, _, _)| p == 1).unwrap();
+    g.select_move(domino);
+    // Color 1 may still take its corner with the monomino.
+    assert!(!g.must_pass());
+    assert_eq!(g.play(None), Err(BlokusError::IllegalPass));
+    g.select_move(0);
+    assert!(g.must_pass());
+    assert!(g.is_over());
+    assert_eq!(g.move_count(), 0);
+}
+
+#[test]
+fn test_eight_colors() {
+    assert_eq!(Game::standard(14, 9, 3).err(), Some(BlokusError::TooManyColors { colors: 9, max: 8 }));
+    assert_eq!(Game::standard(14, 1, 3).err(), Some(BlokusError::UnsupportedGame { size: 14, colors: 1, piece_size: 3 }));
+    assert!(Game::standard(101, 2, 3).is_err());
+    assert!(Game::standard(14, 2, 7).is_err());
+    // The corners and sides of a 2x2 board give only four homes.
+    assert_eq!(Game::standard(2, 5, 1).err(), Some(BlokusError::BadHome((0, 0))));
+    let mut g = Game::standard(14, 8, 3).unwrap();
+    assert_eq!(g.board().colors(), 8);
+    while g.move_count() > 0 {
+        g.select_move(0);
+    }
+    // Every color got to place pieces without disturbing the others.
+    assert!(g.scores().iter().all(|&s| s > -10));
+    assert!((0..g.board().size()).any(|i| g.board().at(i) == Some(7)));
+    assert!(g.board().to_string().contains("\x1B[92m"));
+}
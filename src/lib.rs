@@ -0,0 +1,25 @@
+extern crate rand;
+
+pub mod neighbors;
+pub mod pieces;
+pub mod symmetry;
+pub mod board;
+pub mod error;
+pub mod treesearch;
+pub mod checkpoint;
+pub mod telemetry;
+pub mod schedule;
+pub mod policy;
+pub mod eval;
+pub mod objective;
+pub mod game;
+pub mod minimax;
+pub mod mcts;
+pub mod notation;
+pub mod agent;
+pub mod rating;
+pub mod encoding;
+pub mod protocol;
+pub mod puct;
+pub mod nn;
+pub mod selfplay;
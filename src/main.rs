@@ -1,18 +1,59 @@
+extern crate blokus;
 extern crate rand;
 
-mod neighbors;
-mod pieces;
-use pieces::{compute_pieces};
-mod board;
-use board::Board;
-mod treesearch;
-use treesearch::{GameStateMut, run_treesearch};
+use std::collections::HashSet;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use rand::{SeedableRng, thread_rng, Rng};
+use rand::rngs::StdRng;
+
+use blokus::checkpoint::{Checkpoint, reseed};
+use blokus::pieces::compute_pieces;
+use blokus::board::{Board, Moves, Rules, StartRule};
+use blokus::error::BlokusError;
+use blokus::neighbors::Topology;
+use blokus::treesearch::{GameStateMut, PlayoutPolicy, Tree, resume_treesearch};
+use blokus::policy::{BoardState, parse_policy};
+use blokus::eval::WeightedEvaluator;
+use blokus::objective::{DEFAULT_OBJECTIVE, Objective, Regions};
+use blokus::schedule::{DEFAULT_SCHEDULE, Schedule, parse_schedule};
+use blokus::telemetry::{Csv, JsonLines, StatusLine, Telemetry};
 
 struct BoardStateMut {
     board: Board,
     count: usize,
-    homes: Vec<(i8, i8)>,
+    objective: Objective,
     best: f64,
+    /// The board with the best value, as printed.
+    best_solution: String,
+    /// Positions whose value is within `threshold` of the best are printed.
+    threshold: f64,
+    /// Moves leading to mirror images of each other that keep every color
+    /// in place need only be tried once. Off when the board has no such
+    /// symmetry, as the standard board with homes in the corners.
+    reduce_symmetry: bool,
+    /// Canonical hashes of the positions printed so far.
+    printed: HashSet<u64>,
+    /// Corrects the objective of playouts that are cut off. Its weights are
+    /// in units of the objective, and the correction is the total over all
+    /// colors since they cooperate.
+    evaluator: WeightedEvaluator,
+}
+
+impl BoardState for BoardStateMut {
+    fn moves(&mut self) -> Moves<'_> {
+        let c = (self.count % self.board.colors()) as u8;
+        let mut moves = self.board.moves(c).unwrap();
+        if self.reduce_symmetry {
+            moves.dedup_symmetric();
+        }
+        moves
+    }
 }
 
 impl GameStateMut for BoardStateMut {
@@ -22,90 +63,340 @@ impl GameStateMut for BoardStateMut {
     }
 
     fn move_count(&mut self) -> usize {
-        let c = (self.count % self.homes.len()) as u8;
-        self.board.moves(c).unwrap().moves.len()
+        self.moves().moves.len()
     }
 
     fn select_move(&mut self, i: usize) {
-        let c = (self.count % self.homes.len()) as u8;
-        let moves = self.board.moves(c).unwrap();
-        if i >= moves.moves.len() {
-            panic!("Only {} moves but tried to select number {}", moves.moves.len(), i);
-        }
-        moves.place(i);
+        self.moves().place(i).expect("move index out of range");
         self.count += 1;
     }
 
     fn value(&mut self) -> f64 {
-        let mut flags = vec![0; self.board.size()];
-        let occupied = 1u8;
-        let visited = 2u8;
-        let mut stack = Vec::new();
-        let mut eightway_stack = Vec::new();
-        let mut cardinal_stack = Vec::new();
-        let mut occupied_count = 0;
-        for i in 0..flags.len() {
-            if self.board.at(i) == None {
-                stack.push(i);
-            } else {
-                occupied_count += 1;
-                flags[i] |= occupied;
+        let regions = Regions::new(&self.board);
+        let value = self.objective.evaluate(&regions);
+        if value >= self.best - self.threshold && self.printed.insert(self.board.canonical_hash()) {
+            let s = format!("{}, value = {}\n{}\n", regions, value, self.board);
+            if value > self.best {
+                self.best = value;
+                self.best_solution = s.clone();
             }
-        }
-        let mut eightway_component_count = 0;
-        let mut cardinal_component_count = 0;
-        while let Some(i) = stack.pop() {
-            if flags[i] != 0 {
-                continue;
-            }
-            eightway_component_count += 1;
-            eightway_stack.push(i);
-            while let Some(i) = eightway_stack.pop() {
-                if flags[i] != 0 {
-                    continue;
-                }
-                cardinal_component_count += 1;
-                cardinal_stack.push(i);
-                while let Some(i) = cardinal_stack.pop() {
-                    if flags[i] != 0 {
-                        continue;
-                    }
-                    flags[i] |= visited;
-                    for j in self.board.cardinal_neighbors(i) {
-                        if flags[j] == 0 {
-                            cardinal_stack.push(j);
-                        }
-                    }
-                    for j in self.board.diagonal_neighbors(i) {
-                        if flags[j] == 0 {
-                            eightway_stack.push(j);
-                        }
-                    }
-                }
-            }
-        }
-        let value = (occupied_count as f64) - 3.0 * (eightway_component_count as f64) - (cardinal_component_count as f64);
-        if value >= self.best - 2.0 {
-            self.best = self.best.max(value);
-            let s = format!("occupied = {}, eightway = {}, cardinal = {}, value = {}\n{}\n", occupied_count, eightway_component_count, cardinal_component_count, value, self.board);
             println!("\r\x1B[K{}", s);
         }
         value
     }
+
+    fn heuristic(&mut self) -> f64 {
+        // Start from the objective so far so that cut-off playouts are
+        // averaged with finished ones in the same units.
+        let regions = Regions::new(&self.board);
+        let correction = self.evaluator.evaluate_all(&self.board).iter().sum::<f64>();
+        self.objective.evaluate(&regions) + correction
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("Usage: blokus [--policy uniform|biggest[:EPSILON]|corners[:EPSILON]]");
+    eprintln!("              [--playout-depth N] [--eval FEATURE=WEIGHT,...]");
+    eprintln!("              [--objective EXPR] [--threshold T] [--schedule SCHEDULE] [--map FILE]");
+    eprintln!("              [--topology plane|cylinder|cylinder-y|torus] [--start RULE]");
+    eprintln!("              [--edge-contact true] [--any-corner true] [--teams T,T,...] [--seed S]");
+    eprintln!("              [--checkpoint FILE] [--checkpoint-interval SECONDS] [--solution FILE] [--memory MB]");
+    eprintln!("              [--progress-interval SECONDS] [--quiet true] [--log-json FILE] [--log-csv FILE]");
+    eprintln!("       blokus --resume FILE [--checkpoint FILE] [--checkpoint-interval SECONDS] [--solution FILE]");
+    eprintln!("              [--memory MB] [--progress-interval SECONDS] [--quiet true] [--log-json FILE] [--log-csv FILE]");
+    eprintln!();
+    eprintln!("EXPR combines numbers and the terms occupied, eightway, cardinal, holes(K),");
+    eprintln!("isolated, largest and area(COLOR) with +, -, * and parentheses.");
+    eprintln!("The default is {:?}.", DEFAULT_OBJECTIVE);
+    eprintln!();
+    eprintln!("A playout cut off after --playout-depth moves is valued by the objective");
+    eprintln!("of the position reached plus the --eval features, weighted in units of the");
+    eprintln!("objective. By default no features are added.");
+    eprintln!();
+    eprintln!("The temperature of the search rises by SCHEDULE, one of constant:T,");
+    eprintln!("linear:RATE[:START], exponential:START:FACTOR, logarithmic:SCALE and");
+    eprintln!("restart:PERIOD:SCHEDULE, in iterations. The default is {:?}.", DEFAULT_SCHEDULE);
+    eprintln!();
+    eprintln!("The board is 20x20 with four colors starting in the corners, or drawn in");
+    eprintln!("the --map FILE with a line per row: '.' for an open cell, '#' for a neutral");
+    eprintln!("cell, ' ' or '_' for a hole and a digit for the home of that color.");
+    eprintln!("With --topology the left and right edges, the top and bottom edges");
+    eprintln!("(cylinder-y) or both (torus) are joined, so that pieces and regions");
+    eprintln!("continue on the other side. The first piece of each color covers its home,");
+    eprintln!("or with --start any cell on the edge, any cell, or one of the given cells:");
+    eprintln!("RULE is home, edge, anywhere or cells:X,Y;X,Y;... counting from 0.");
+    eprintln!("Pieces may also touch the sides of their color with --edge-contact, connect");
+    eprintln!("to the corners of any color with --any-corner, and treat the colors of their");
+    eprintln!("team as their own with --teams, which gives the team of each color.");
+    eprintln!();
+    eprintln!("With --checkpoint the search is saved every 600 seconds by default, and");
+    eprintln!("--resume continues a saved search with its options. On Ctrl-C the search");
+    eprintln!("stops, saves a last checkpoint and writes the best solution to --solution");
+    eprintln!("or standard output. With --memory, the least promising parts of the search");
+    eprintln!("tree are pruned to keep it within the given number of megabytes.");
+    eprintln!();
+    eprintln!("Progress is shown on a status line on standard error every second,");
+    eprintln!("unless --quiet is given, and can be logged as JSON lines or CSV.");
+    process::exit(2);
+}
+
+/// The options that define the search, which are saved in checkpoints.
+struct SearchConfig {
+    policy: Box<dyn PlayoutPolicy<BoardStateMut>>,
+    playout_depth: Option<usize>,
+    objective: Objective,
+    threshold: f64,
+    evaluator: WeightedEvaluator,
+    schedule: Box<dyn Schedule>,
+    /// The map of the board, or `None` for the standard board.
+    map: Option<String>,
+    topology: Topology,
+    rules: Rules,
+}
+
+fn parse_search_args(args: &[String]) -> SearchConfig {
+    let mut config = SearchConfig {
+        policy: parse_policy("uniform").unwrap(),
+        playout_depth: None,
+        objective: Objective::default(),
+        threshold: 2.0,
+        evaluator: WeightedEvaluator { weights: Vec::new(), relative: false },
+        schedule: parse_schedule(DEFAULT_SCHEDULE).unwrap(),
+        map: None,
+        topology: Topology::PLANE,
+        rules: Rules::default(),
+    };
+    for pair in args.chunks(2) {
+        let (arg, value) = (&pair[0], &pair[1]);
+        match arg.as_str() {
+            "--policy" => {
+                config.policy = parse_policy(value).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    usage()
+                });
+            },
+            "--playout-depth" => {
+                config.playout_depth = Some(value.parse().unwrap_or_else(|_| usage()));
+            },
+            "--eval" => {
+                config.evaluator = WeightedEvaluator::parse(value).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    usage()
+                });
+                config.evaluator.relative = false;
+            },
+            "--objective" => {
+                config.objective = Objective::parse(value).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    usage()
+                });
+            },
+            "--threshold" => {
+                config.threshold = value.parse().unwrap_or_else(|_| usage());
+            },
+            "--schedule" => {
+                config.schedule = parse_schedule(value).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    usage()
+                });
+            },
+            // `main` replaces `--map FILE` by the contents of the file.
+            "--map-text" => {
+                config.map = Some(value.clone());
+            },
+            "--start" => {
+                config.rules.start = StartRule::parse(value).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    usage()
+                });
+            },
+            "--edge-contact" => {
+                config.rules.edge_contact = value.parse().unwrap_or_else(|_| usage());
+            },
+            "--any-corner" => {
+                config.rules.any_corner = value.parse().unwrap_or_else(|_| usage());
+            },
+            "--teams" => {
+                config.rules.teams = value.split(',').map(|t| t.trim().parse().unwrap_or_else(|_| usage())).collect();
+            },
+            "--topology" => {
+                config.topology = Topology::parse(value).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    usage()
+                });
+            },
+            _ => usage(),
+        }
+    }
+    config
+}
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Set `INTERRUPTED` on SIGINT instead of terminating.
+#[cfg(unix)]
+fn catch_interrupt() {
+    extern "C" fn handle(_signal: i32) {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+    }
+    extern "C" {
+        fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+    }
+    const SIGINT: i32 = 2;
+    unsafe {
+        signal(SIGINT, handle);
+    }
+}
+
+#[cfg(not(unix))]
+fn catch_interrupt() {}
+
+fn fail<E: std::fmt::Display>(path: &str, e: E) -> ! {
+    eprintln!("{}: {}", path, e);
+    process::exit(1);
 }
 
 fn main() {
+    let mut search_args = Vec::new();
+    let mut seed = None;
+    let mut resume = None;
+    let mut checkpoint_path = None;
+    let mut interval = Duration::from_secs(600);
+    let mut solution_path = None;
+    let mut memory = None;
+    let mut progress_interval = Duration::from_secs(1);
+    let mut quiet = false;
+    let mut json_log = None;
+    let mut csv_log = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--seed" => seed = Some(value.parse::<u64>().unwrap_or_else(|_| usage())),
+            "--resume" => resume = Some(value),
+            "--checkpoint" => checkpoint_path = Some(value),
+            "--checkpoint-interval" => interval = Duration::from_secs(value.parse().unwrap_or_else(|_| usage())),
+            "--solution" => solution_path = Some(value),
+            "--memory" => memory = Some(value.parse::<usize>().unwrap_or_else(|_| usage()) << 20),
+            "--progress-interval" => {
+                let seconds = value.parse::<f64>().ok().filter(|&t| t >= 0.0).unwrap_or_else(|| usage());
+                progress_interval = Duration::from_secs_f64(seconds);
+            },
+            "--quiet" => quiet = value.parse().unwrap_or_else(|_| usage()),
+            "--log-json" => json_log = Some(value),
+            "--log-csv" => csv_log = Some(value),
+            _ => search_args.extend(vec![arg, value]),
+        }
+    }
+    let resumed = resume.map(|path| {
+        if !search_args.is_empty() || seed.is_some() {
+            usage();
+        }
+        let checkpoint = Checkpoint::load(&path).unwrap_or_else(|e| fail(&path, e));
+        if checkpoint.args.len() % 2 != 0 {
+            fail(&path, "invalid options in checkpoint");
+        }
+        search_args = checkpoint.args.clone();
+        checkpoint
+    });
+    // Checkpoints keep the map itself, so that resuming does not need the file.
+    if resumed.is_none() {
+        for pair in search_args.chunks_mut(2) {
+            if pair[0] == "--map" {
+                let map = fs::read_to_string(&pair[1]).unwrap_or_else(|e| fail(&pair[1], e));
+                pair[0] = "--map-text".to_string();
+                pair[1] = map;
+            }
+        }
+    }
+    let config = parse_search_args(&search_args);
+
     let pieces = compute_pieces(5);
-    let width = 20;
-    let height = 20;
-    let needed_tiles = 4 * pieces.iter().map(|p| p.points.len()).sum::<usize>();
-    if width * height < needed_tiles {
-        panic!("Need {} tiles but have only {}*{}", needed_tiles, width, height);
-    }
-    let homes = vec![(0, 0), (width as i8 - 1, 0), (width as i8 - 1, height as i8 - 1), (0, height as i8 - 1)];
-    let mut rng = rand::thread_rng();
-    let b = Board::new(pieces.clone(), height, width, &homes);
+    let b = match config.map {
+        Some(ref map) => Board::from_map(pieces, map).unwrap_or_else(|e| fail("--map", e)),
+        None => {
+            let (width, height) = (20, 20);
+            let homes = [(0, 0), (width - 1, 0), (width - 1, height - 1), (0, height - 1)];
+            Board::new(pieces, height as usize, width as usize, &homes).unwrap_or_else(|e| fail("blokus", e))
+        },
+    };
+    let b = b.with_topology(config.topology)
+        .and_then(|b| b.with_rules(config.rules.clone()))
+        .unwrap_or_else(|e| fail("blokus", e));
+    let open_cells = (0..b.size()).filter(|&i| b.is_vacant(i)).count();
+    let needed_tiles = (0..b.colors()).map(|c| b.remaining_squares(c as u8)).sum::<usize>();
+    if open_cells < needed_tiles {
+        fail("blokus", BlokusError::BoardTooSmall { cells: open_cells, needed: needed_tiles });
+    }
     //println!("{:?}", b);
-    let mut s = BoardStateMut {board: b, count: 0, homes: homes, best: 0.0};
-    run_treesearch(&mut s, &mut rng, 0.00001);
+    println!("Maximizing {}", config.objective);
+    let reduce_symmetry = b.has_color_fixing_symmetry();
+    let mut s = BoardStateMut {
+        board: b, count: 0, objective: config.objective, best: f64::NEG_INFINITY, best_solution: String::new(),
+        threshold: config.threshold, reduce_symmetry, printed: HashSet::new(), evaluator: config.evaluator,
+    };
+    let schedule = config.schedule;
+    let (mut rng, mut tree) = match resumed {
+        Some(checkpoint) => {
+            let iterations = checkpoint.tree.visits();
+            println!("Resuming after {} iterations at T={:.4}", iterations, schedule.temperature(iterations));
+            s.best = checkpoint.best;
+            s.best_solution = checkpoint.best_solution;
+            s.printed = checkpoint.printed.into_iter().collect();
+            (StdRng::seed_from_u64(checkpoint.seed), checkpoint.tree)
+        },
+        None => (StdRng::seed_from_u64(seed.unwrap_or_else(|| thread_rng().gen())), Tree::new()),
+    };
+    tree.set_memory_budget(memory);
+    let mut telemetry = Telemetry::new(progress_interval);
+    if !quiet {
+        telemetry.add_observer(StatusLine(io::stderr()));
+    }
+    if let Some(ref path) = json_log {
+        telemetry.add_observer(JsonLines(BufWriter::new(File::create(path).unwrap_or_else(|e| fail(path, e)))));
+    }
+    if let Some(ref path) = csv_log {
+        telemetry.add_observer(Csv::new(BufWriter::new(File::create(path).unwrap_or_else(|e| fail(path, e)))));
+    }
+    if s.best.is_finite() {
+        telemetry.set_best_value(s.best);
+    }
+
+    catch_interrupt();
+    let save = |s: &BoardStateMut, tree: &Tree, rng: &mut StdRng| {
+        if let Some(ref path) = checkpoint_path {
+            let checkpoint = Checkpoint {
+                args: search_args.clone(),
+                seed: reseed(rng),
+                best: s.best,
+                best_solution: s.best_solution.clone(),
+                printed: s.printed.iter().cloned().collect(),
+                tree: tree.clone(),
+            };
+            checkpoint.save(path).unwrap_or_else(|e| fail(path, e));
+        }
+        if let Some(ref path) = solution_path {
+            fs::write(path, &s.best_solution).unwrap_or_else(|e| fail(path, e));
+        }
+    };
+    let mut last_save = Instant::now();
+    let tree = resume_treesearch(&mut s, config.policy, config.playout_depth, &mut rng, &*schedule, tree,
+                                 &mut telemetry, |s, tree, rng| {
+        if INTERRUPTED.load(Ordering::SeqCst) {
+            return false;
+        }
+        if last_save.elapsed() >= interval {
+            save(s, tree, rng);
+            last_save = Instant::now();
+        }
+        true
+    });
+    save(&s, &tree, &mut rng);
+    for e in telemetry.errors() {
+        eprintln!("Progress reports stopped: {}", e);
+    }
+    println!("\r\x1B[KStopped after {} iterations with {} nodes", tree.visits(), tree.node_count());
+    if solution_path.is_none() {
+        print!("Best: {}", s.best_solution);
+    }
 }
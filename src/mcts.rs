@@ -0,0 +1,212 @@
+//! Monte Carlo tree search for competitive games.
+//!
+//! The search of `treesearch` maximizes a single value, which suits the
+//! cooperative tiling search but has every color in the tree play for the
+//! color at the root. Here each node holds the average outcome for the color
+//! that made the move leading to it, so every color chooses the moves that are
+//! best for itself. A new leaf is valued by playing uniformly random moves to
+//! the end of the game, and children are selected by UCT
+//!
+//! ```text
+//! Q(child) + exploration * sqrt(ln N(node) / N(child))
+//! ```
+//!
+//! where `Q` is the average outcome between -1 and 1 of `game::outcomes`
+//! and `N` counts visits. Children that were never visited are tried first.
+
+use rand::Rng;
+
+use board::{Color, Move};
+use game::{Game, outcomes};
+use treesearch::GameStateMut;
+
+struct Node {
+    /// The move leading here from the parent; `None` is a pass.
+    mv: Option<Move>,
+    /// The color `value_sum` is counted for: the color that made `mv`,
+    /// or the color to move at the root.
+    color: Color,
+    visits: u64,
+    value_sum: f64,
+    /// Indices of the children, once the node has been expanded.
+    children: Option<Vec<usize>>,
+}
+
+impl Node {
+    fn new(mv: Option<Move>, color: Color) -> Self {
+        Node { mv, color, visits: 0, value_sum: 0.0, children: None }
+    }
+}
+
+/// A search tree rooted at the position of the game it was created for.
+pub struct MctsSearch {
+    nodes: Vec<Node>,
+    exploration: f64,
+}
+
+impl MctsSearch {
+    pub fn new(game: &Game, exploration: f64) -> Self {
+        MctsSearch { nodes: vec![Node::new(None, game.to_move())], exploration }
+    }
+
+    /// Number of simulations run from the root.
+    pub fn visits(&self) -> u64 {
+        self.nodes[0].visits
+    }
+
+    fn select_child(&self, i: usize) -> usize {
+        let children = self.nodes[i].children.as_ref().unwrap();
+        if let Some(&c) = children.iter().find(|&&c| self.nodes[c].visits == 0) {
+            return c;
+        }
+        let log_visits = (self.nodes[i].visits as f64).ln();
+        let score = |c: usize| {
+            let child = &self.nodes[c];
+            let visits = child.visits as f64;
+            child.value_sum / visits + self.exploration * (log_visits / visits).sqrt()
+        };
+        let mut best = children[0];
+        for &c in children.iter() {
+            if score(c) > score(best) {
+                best = c;
+            }
+        }
+        best
+    }
+
+    /// Add the children of leaf `i`, which is the current position of `game`.
+    fn expand(&mut self, i: usize, game: &mut Game) {
+        let color = game.to_move();
+        let moves = if game.must_pass() { vec![None] } else { game.legal_moves().iter().map(|&m| Some(m)).collect() };
+        let mut children = Vec::new();
+        for mv in moves {
+            children.push(self.nodes.len());
+            self.nodes.push(Node::new(mv, color));
+        }
+        self.nodes[i].children = Some(children);
+    }
+
+    /// Play random moves to the end of the game and return the outcome for
+    /// each color; `game` is back in its position afterwards.
+    fn playout<R: Rng + ?Sized>(game: &mut Game, rng: &mut R) -> Vec<f64> {
+        let mut played = 0;
+        loop {
+            let count = game.move_count();
+            if count == 0 {
+                break;
+            }
+            game.select_move(rng.gen_range(0, count));
+            played += 1;
+        }
+        let res = outcomes(&game.scores());
+        for _ in 0..played {
+            game.take_back();
+        }
+        res
+    }
+
+    /// Run one simulation from the root; `game` is back in the root position afterwards.
+    pub fn iterate<R: Rng + ?Sized>(&mut self, game: &mut Game, rng: &mut R) {
+        let mut path = vec![0];
+        let mut i = 0;
+        while self.nodes[i].children.is_some() && game.move_count() > 0 {
+            i = self.select_child(i);
+            game.play(self.nodes[i].mv).unwrap();
+            path.push(i);
+        }
+        if game.move_count() > 0 {
+            self.expand(i, game);
+            i = self.select_child(i);
+            game.play(self.nodes[i].mv).unwrap();
+            path.push(i);
+        }
+        let values = Self::playout(game, rng);
+        for &i in path.iter() {
+            let node = &mut self.nodes[i];
+            node.visits += 1;
+            node.value_sum += values[node.color as usize];
+        }
+        for _ in 1..path.len() {
+            game.take_back();
+        }
+    }
+
+    pub fn run<R: Rng + ?Sized>(&mut self, game: &mut Game, rng: &mut R, iterations: usize) {
+        for _ in 0..iterations {
+            self.iterate(game, rng);
+        }
+    }
+
+    /// The moves at the root with their visit counts.
+    pub fn root_visits(&self) -> Vec<(Option<Move>, u64)> {
+        match self.nodes[0].children {
+            Some(ref children) => children.iter().map(|&c| (self.nodes[c].mv, self.nodes[c].visits)).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// The most visited move at the root, or `None` before the first iteration.
+    pub fn best_move(&self) -> Option<Option<Move>> {
+        self.root_visits().into_iter().max_by_key(|&(_, visits)| visits).map(|(mv, _)| mv)
+    }
+
+    /// Keep only the subtree of root move `mv`, as the search of the position
+    /// after that move, or `None` if the search has not tried the move.
+    pub fn reroot(self, mv: Option<Move>) -> Option<Self> {
+        let root = *self.nodes[0].children.as_ref()?.iter().find(|&&c| self.nodes[c].mv == mv)?;
+        let mut nodes = self.nodes.into_iter().map(Some).collect::<Vec<_>>();
+        let mut res = MctsSearch { nodes: Vec::new(), exploration: self.exploration };
+        // Copy the subtree in breadth-first order, where the children of a
+        // node get the indices following those already copied.
+        let mut queue = vec![root];
+        let mut next = 0;
+        while next < queue.len() {
+            let mut node = nodes[queue[next]].take().unwrap();
+            next += 1;
+            if let Some(ref mut children) = node.children {
+                for c in children.iter_mut() {
+                    queue.push(*c);
+                    *c = queue.len() - 1;
+                }
+            }
+            res.nodes.push(node);
+        }
+        Some(res)
+    }
+}
+
+#[test]
+fn test_mcts_finds_winning_move() {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    // See `minimax::corridor`: playing the domino first wins on a 1x3 board.
+    let mut g = Game::new(::board::Board::new(::pieces::compute_pieces(2), 1, 3, &[(0, 0), (2, 0)]).unwrap());
+    let mut search = MctsSearch::new(&g, 1.0);
+    search.run(&mut g, &mut StdRng::seed_from_u64(1), 200);
+    assert!(g.history().is_empty());
+    assert_eq!(search.visits(), 200);
+    assert_eq!(search.root_visits().iter().map(|&(_, v)| v).sum::<u64>(), 200);
+    let (piece, _, _) = search.best_move().unwrap().unwrap();
+    assert_eq!(piece, 1);
+}
+
+#[test]
+fn test_opponents_play_for_themselves() {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    // After color 0 plays the monomino on the 1x3 board, color 1 wins with its
+    // domino. A search for color 0 alone would have color 1 play its monomino.
+    let mut g = Game::new(::board::Board::new(::pieces::compute_pieces(2), 1, 3, &[(0, 0), (2, 0)]).unwrap());
+    let mono = g.legal_moves().iter().cloned().find(|&(p, _, _)| p == 0).unwrap();
+    let mut search = MctsSearch::new(&g, 1.0);
+    search.run(&mut g, &mut StdRng::seed_from_u64(2), 300);
+    let visits = search.root_visits().iter().find(|&&(mv, _)| mv == Some(mono)).unwrap().1;
+    let mut search = search.reroot(Some(mono)).unwrap();
+    assert_eq!(search.visits(), visits);
+    g.play(Some(mono)).unwrap();
+    search.run(&mut g, &mut StdRng::seed_from_u64(3), 100);
+    let (piece, _, _) = search.best_move().unwrap().unwrap();
+    assert_eq!(piece, 1);
+    assert_eq!(search.visits(), visits + 100);
+    assert!(search.reroot(None).is_none());
+}
@@ -0,0 +1,270 @@
+use std::time::{Duration, Instant};
+
+use game::Game;
+use treesearch::GameStateMut;
+
+/// A game state with several players taking turns, for the classic searchers.
+pub trait MultiPlayerState: GameStateMut {
+    fn players(&self) -> usize;
+    fn player_to_move(&self) -> usize;
+    /// Value of the position for every player, higher is better:
+    /// the exact outcome if the game is over, and a heuristic estimate otherwise.
+    fn evaluate(&mut self) -> Vec<f64>;
+    /// Indices of the moves in the order they should be searched.
+    fn ordered_moves(&mut self) -> Vec<usize> {
+        (0..self.move_count()).collect()
+    }
+}
+
+impl MultiPlayerState for Game {
+    fn players(&self) -> usize {
+        self.board().colors()
+    }
+
+    fn player_to_move(&self) -> usize {
+        self.to_move() as usize
+    }
+
+    fn evaluate(&mut self) -> Vec<f64> {
+        if !self.is_over() {
+            return self.evaluator.evaluate_all(self.board());
+        }
+        let scores = self.scores();
+        let total = scores.iter().sum::<i32>() as f64;
+        let others = (scores.len().max(2) - 1) as f64;
+        scores.iter().map(|&s| s as f64 - (total - s as f64) / others).collect()
+    }
+
+    /// Biggest pieces first.
+    fn ordered_moves(&mut self) -> Vec<usize> {
+        if self.must_pass() {
+            return (0..self.move_count()).collect();
+        }
+        let legal = self.legal_moves().to_vec();
+        let sizes = legal.iter().map(|&(p, _, _)| self.board().piece_size(p)).collect::<Vec<_>>();
+        let mut order = (0..sizes.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| ::std::cmp::Reverse(sizes[i]));
+        order
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Alpha-beta where every opponent is assumed to minimize the value of the root player.
+    Paranoid,
+    /// Every player maximizes its own component of the value vector.
+    MaxN,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SearchLimits {
+    /// Maximum number of plies, passes included.
+    pub max_depth: usize,
+    pub time_limit: Option<Duration>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    /// Index of the best move for `select_move`, or `None` if there are no moves.
+    pub best_move: Option<usize>,
+    /// Value of the best move for the player to move.
+    pub value: f64,
+    /// Depth of the deepest completed iteration.
+    pub depth: usize,
+    pub nodes: usize,
+}
+
+struct Searcher<'a, G: MultiPlayerState + 'a> {
+    state: &'a mut G,
+    algorithm: Algorithm,
+    deadline: Option<Instant>,
+    root_player: usize,
+    nodes: usize,
+    aborted: bool,
+    /// Set if the current iteration stopped at the depth limit somewhere.
+    hit_horizon: bool,
+}
+
+impl<'a, G: MultiPlayerState> Searcher<'a, G> {
+    fn out_of_time(&mut self) -> bool {
+        if !self.aborted {
+            if let Some(deadline) = self.deadline {
+                self.aborted = Instant::now() >= deadline;
+            }
+        }
+        self.aborted
+    }
+
+    fn leaf(&mut self, depth: usize) -> Option<Vec<f64>> {
+        if self.state.move_count() == 0 {
+            return Some(self.state.evaluate());
+        }
+        if depth == 0 {
+            self.hit_horizon = true;
+            return Some(self.state.evaluate());
+        }
+        None
+    }
+
+    fn paranoid(&mut self, depth: usize, mut alpha: f64, mut beta: f64) -> f64 {
+        self.nodes += 1;
+        if self.out_of_time() {
+            return 0.0;
+        }
+        if let Some(v) = self.leaf(depth) {
+            return v[self.root_player];
+        }
+        let maximizing = self.state.player_to_move() == self.root_player;
+        let mut best = if maximizing { f64::NEG_INFINITY } else { f64::INFINITY };
+        for i in self.state.ordered_moves() {
+            self.state.select_move(i);
+            let v = self.paranoid(depth - 1, alpha, beta);
+            self.state.undo();
+            if self.aborted {
+                return 0.0;
+            }
+            if maximizing {
+                best = best.max(v);
+                alpha = alpha.max(v);
+            } else {
+                best = best.min(v);
+                beta = beta.min(v);
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+        best
+    }
+
+    fn maxn(&mut self, depth: usize) -> Vec<f64> {
+        self.nodes += 1;
+        if self.out_of_time() {
+            return Vec::new();
+        }
+        if let Some(v) = self.leaf(depth) {
+            return v;
+        }
+        let player = self.state.player_to_move();
+        let mut best: Option<Vec<f64>> = None;
+        for i in self.state.ordered_moves() {
+            self.state.select_move(i);
+            let v = self.maxn(depth - 1);
+            self.state.undo();
+            if self.aborted {
+                return Vec::new();
+            }
+            if best.as_ref().map_or(true, |b| v[player] > b[player]) {
+                best = Some(v);
+            }
+        }
+        best.unwrap()
+    }
+
+    /// Search all root moves to the given depth, trying `first` first.
+    /// Returns the best move and its value, or `None` if aborted.
+    fn root(&mut self, depth: usize, first: Option<usize>) -> Option<(usize, f64)> {
+        let mut order = self.state.ordered_moves();
+        if let Some(first) = first {
+            order.retain(|&i| i != first);
+            order.insert(0, first);
+        }
+        let mut best: Option<(usize, f64)> = None;
+        for i in order {
+            self.state.select_move(i);
+            let v = match self.algorithm {
+                Algorithm::Paranoid => {
+                    let alpha = best.map_or(f64::NEG_INFINITY, |(_, v)| v);
+                    self.paranoid(depth - 1, alpha, f64::INFINITY)
+                },
+                Algorithm::MaxN => {
+                    let v = self.maxn(depth - 1);
+                    if self.aborted { 0.0 } else { v[self.root_player] }
+                },
+            };
+            self.state.undo();
+            if self.aborted {
+                return None;
+            }
+            if best.map_or(true, |(_, b)| v > b) {
+                best = Some((i, v));
+            }
+        }
+        best
+    }
+}
+
+/// Iterative deepening search for the player to move in `state`.
+/// Returns the result of the deepest iteration that finished within the time limit;
+/// if not even the first iteration finishes, the first move in search order is returned.
+pub fn search<G: MultiPlayerState>(state: &mut G, algorithm: Algorithm, limits: SearchLimits) -> SearchResult {
+    let root_player = state.player_to_move();
+    let mut searcher = Searcher {
+        state,
+        algorithm,
+        deadline: limits.time_limit.map(|t| Instant::now() + t),
+        root_player,
+        nodes: 0,
+        aborted: false,
+        hit_horizon: false,
+    };
+    let mut result = SearchResult {
+        best_move: searcher.state.ordered_moves().first().cloned(),
+        value: 0.0,
+        depth: 0,
+        nodes: 0,
+    };
+    if result.best_move.is_none() {
+        return result;
+    }
+    for depth in 1..(limits.max_depth + 1) {
+        searcher.hit_horizon = false;
+        match searcher.root(depth, result.best_move) {
+            Some((best_move, value)) => {
+                result.best_move = Some(best_move);
+                result.value = value;
+                result.depth = depth;
+            },
+            None => break,
+        }
+        if !searcher.hit_horizon {
+            // The whole game tree was searched.
+            break;
+        }
+    }
+    result.nodes = searcher.nodes;
+    result
+}
+
+#[cfg(test)]
+fn corridor() -> Game {
+    // On a 1x3 board, color 0 starts at the left end and color 1 at the right.
+    // Playing the domino first wins for color 0: color 1 then only fits its monomino.
+    Game::new(::board::Board::new(::pieces::compute_pieces(2), 1, 3, &[(0, 0), (2, 0)]).unwrap())
+}
+
+#[test]
+fn test_search_finds_winning_move() {
+    for &algorithm in &[Algorithm::Paranoid, Algorithm::MaxN] {
+        let mut g = corridor();
+        let limits = SearchLimits { max_depth: 10, time_limit: None };
+        let result = search(&mut g, algorithm, limits);
+        let (piece, _, _) = g.legal_moves()[result.best_move.unwrap()];
+        assert_eq!(piece, 1, "{:?}", algorithm);
+        assert!(result.value > 0.0);
+        // The game ends within four plies, so deeper iterations are skipped.
+        assert!(result.depth <= 4);
+        assert!(g.history().is_empty());
+    }
+}
+
+#[test]
+fn test_search_respects_time_limit() {
+    let mut g = Game::new(::board::Board::new(::pieces::compute_pieces(5), 14, 14, &[(0, 0), (13, 13)]).unwrap());
+    let limits = SearchLimits { max_depth: 100, time_limit: Some(Duration::from_millis(50)) };
+    let start = Instant::now();
+    let result = search(&mut g, Algorithm::Paranoid, limits);
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(result.best_move.is_some());
+    assert!(g.history().is_empty());
+}
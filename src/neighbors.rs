@@ -1,101 +1,177 @@
-pub struct CardinalIterator {
+use std::fmt;
+
+use board::Terrain;
+
+/// Which edges of the board are joined to the opposite edge, so that
+/// neighbors and pieces continue on the other side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Topology {
+    /// The left and right edges are joined.
+    pub wrap_x: bool,
+    /// The top and bottom edges are joined.
+    pub wrap_y: bool,
+}
+
+impl Topology {
+    pub const PLANE: Topology = Topology { wrap_x: false, wrap_y: false };
+    /// Wraps around from left to right.
+    pub const CYLINDER: Topology = Topology { wrap_x: true, wrap_y: false };
+    pub const TORUS: Topology = Topology { wrap_x: true, wrap_y: true };
+
+    /// Parse `plane`, `cylinder` (wrapping from left to right), `cylinder-y`
+    /// (wrapping from top to bottom) or `torus`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "plane" => Ok(Topology::PLANE),
+            "cylinder" => Ok(Topology::CYLINDER),
+            "cylinder-y" => Ok(Topology { wrap_x: false, wrap_y: true }),
+            "torus" => Ok(Topology::TORUS),
+            _ => Err(format!("unknown topology {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match (self.wrap_x, self.wrap_y) {
+            (false, false) => "plane",
+            (true, false) => "cylinder",
+            (false, true) => "cylinder-y",
+            (true, true) => "torus",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The coordinate before `v` on an axis of `n` cells, if any.
+fn before(v: usize, n: usize, wrap: bool) -> Option<usize> {
+    if v > 0 {
+        Some(v - 1)
+    } else if wrap {
+        Some(n - 1)
+    } else {
+        None
+    }
+}
+
+/// The coordinate after `v` on an axis of `n` cells, if any.
+fn after(v: usize, n: usize, wrap: bool) -> Option<usize> {
+    if v + 1 < n {
+        Some(v + 1)
+    } else if wrap {
+        Some(0)
+    } else {
+        None
+    }
+}
+
+pub struct CardinalIterator<'a> {
     rows: usize,
     cols: usize,
     x: usize,
     y: usize,
     i: usize,
+    /// Neighbors that are holes are skipped.
+    terrain: &'a [Terrain],
+    topology: Topology,
 }
 
-impl CardinalIterator {
-    pub fn new(rows: usize, cols: usize, x: usize, y: usize) -> Self {
+impl<'a> CardinalIterator<'a> {
+    pub fn new(rows: usize, cols: usize, x: usize, y: usize, terrain: &'a [Terrain], topology: Topology) -> Self {
         CardinalIterator {
-            rows: rows,
-            cols: cols,
-            x: x,
-            y: y,
+            rows,
+            cols,
+            x,
+            y,
             i: 0,
+            terrain,
+            topology,
         }
     }
 }
 
-impl Iterator for CardinalIterator {
+impl<'a> Iterator for CardinalIterator<'a> {
     type Item = usize;
     fn next(&mut self) -> Option<usize> {
-        if self.i == 0 {
+        let Topology { wrap_x, wrap_y } = self.topology;
+        while self.i < 4 {
             self.i += 1;
-            if self.y > 0 {
-                return Some((self.y - 1) * self.cols + self.x);
-            }
-        }
-        if self.i == 1 {
-            self.i += 1;
-            if self.x > 0 {
-                return Some(self.y * self.cols + (self.x - 1));
-            }
-        }
-        if self.i == 2 {
-            self.i += 1;
-            if self.x + 1 < self.cols {
-                return Some(self.y * self.cols + (self.x + 1));
-            }
-        }
-        if self.i == 3 {
-            self.i += 1;
-            if self.y + 1 < self.rows {
-                return Some((self.y + 1) * self.cols + self.x);
+            let neighbor = match self.i {
+                1 => (Some(self.x), before(self.y, self.rows, wrap_y)),
+                2 => (before(self.x, self.cols, wrap_x), Some(self.y)),
+                3 => (after(self.x, self.cols, wrap_x), Some(self.y)),
+                _ => (Some(self.x), after(self.y, self.rows, wrap_y)),
+            };
+            if let (Some(x), Some(y)) = neighbor {
+                let neighbor = y * self.cols + x;
+                if self.terrain[neighbor] != Terrain::Hole {
+                    return Some(neighbor);
+                }
             }
         }
         None
     }
 }
 
-pub struct DiagonalIterator {
+pub struct DiagonalIterator<'a> {
     rows: usize,
     cols: usize,
     x: usize,
     y: usize,
     i: usize,
+    /// Neighbors that are holes are skipped.
+    terrain: &'a [Terrain],
+    topology: Topology,
 }
 
-impl DiagonalIterator {
-    pub fn new(rows: usize, cols: usize, x: usize, y: usize) -> Self {
+impl<'a> DiagonalIterator<'a> {
+    pub fn new(rows: usize, cols: usize, x: usize, y: usize, terrain: &'a [Terrain], topology: Topology) -> Self {
         DiagonalIterator {
-            rows: rows,
-            cols: cols,
-            x: x,
-            y: y,
+            rows,
+            cols,
+            x,
+            y,
             i: 0,
+            terrain,
+            topology,
         }
     }
 }
 
-impl Iterator for DiagonalIterator {
+impl<'a> Iterator for DiagonalIterator<'a> {
     type Item = usize;
     fn next(&mut self) -> Option<usize> {
-        if self.i == 0 {
-            self.i += 1;
-            if self.y > 0 && self.x > 0 {
-                return Some((self.y - 1) * self.cols + (self.x - 1));
-            }
-        }
-        if self.i == 1 {
-            self.i += 1;
-            if self.y + 1 < self.rows && self.x > 0 {
-                return Some((self.y + 1) * self.cols + (self.x - 1));
-            }
-        }
-        if self.i == 2 {
-            self.i += 1;
-            if self.y > 0 && self.x + 1 < self.cols {
-                return Some((self.y - 1) * self.cols + (self.x + 1));
-            }
-        }
-        if self.i == 3 {
+        let Topology { wrap_x, wrap_y } = self.topology;
+        while self.i < 4 {
             self.i += 1;
-            if self.y + 1 < self.rows && self.x + 1 < self.cols {
-                return Some((self.y + 1) * self.cols + (self.x + 1));
+            let neighbor = match self.i {
+                1 => (before(self.x, self.cols, wrap_x), before(self.y, self.rows, wrap_y)),
+                2 => (before(self.x, self.cols, wrap_x), after(self.y, self.rows, wrap_y)),
+                3 => (after(self.x, self.cols, wrap_x), before(self.y, self.rows, wrap_y)),
+                _ => (after(self.x, self.cols, wrap_x), after(self.y, self.rows, wrap_y)),
+            };
+            if let (Some(x), Some(y)) = neighbor {
+                let neighbor = y * self.cols + x;
+                if self.terrain[neighbor] != Terrain::Hole {
+                    return Some(neighbor);
+                }
             }
         }
         None
     }
 }
+
+#[test]
+fn test_wrapping_neighbors() {
+    let terrain = vec![Terrain::Open; 12];
+    let cardinal = |topology| CardinalIterator::new(3, 4, 0, 0, &terrain, topology).collect::<Vec<_>>();
+    assert_eq!(cardinal(Topology::PLANE), vec![1, 4]);
+    assert_eq!(cardinal(Topology::CYLINDER), vec![3, 1, 4]);
+    assert_eq!(cardinal(Topology::TORUS), vec![8, 3, 1, 4]);
+    let diagonal = DiagonalIterator::new(3, 4, 3, 2, &terrain, Topology::TORUS).collect::<Vec<_>>();
+    assert_eq!(diagonal, vec![6, 2, 4, 0]);
+    for s in &["plane", "cylinder", "cylinder-y", "torus"] {
+        assert_eq!(Topology::parse(s).unwrap().to_string(), *s);
+    }
+    assert!(Topology::parse("sphere").is_err());
+}
@@ -0,0 +1,285 @@
+//! A small convolutional network for `puct::Evaluator`, evaluated on the CPU.
+//!
+//! The input is `Perspective::observation`. A stack of 3x3 convolutions with
+//! ReLU feeds two heads: a 1x1 convolution with one channel per piece orientation,
+//! whose outputs are exactly the placement logits in the order of
+//! `encoding`, and a dense layer on the average of each channel, whose outputs
+//! are the expected outcome of each color (through tanh) and the pass logit.
+//!
+//! Weights files are little-endian: the magic `BKNN`, then the `u32`s
+//! version (1), input planes, filters, hidden layers, policy channels and
+//! dense outputs, then `f32`s for each layer in order, weights before biases.
+//! Convolution weights are indexed `[out][in][y][x]` and dense weights `[out][in]`.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use rand::Rng;
+
+use board::Board;
+use encoding::{ORIENTATIONS, Perspective, observation_shape};
+use puct::Evaluator;
+
+const MAGIC: &[u8; 4] = b"BKNN";
+const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Conv {
+    inputs: usize,
+    outputs: usize,
+    /// 1 or 3; 3x3 kernels are zero padded to keep the board size.
+    kernel: usize,
+    weights: Vec<f32>,
+    bias: Vec<f32>,
+}
+
+impl Conv {
+    fn forward(&self, input: &[f32], rows: usize, cols: usize, relu: bool) -> Vec<f32> {
+        let size = rows * cols;
+        let k = self.kernel;
+        let r = (k / 2) as isize;
+        let mut res = vec![0.0; self.outputs * size];
+        for o in 0..self.outputs {
+            let out = &mut res[o * size..(o + 1) * size];
+            for x in out.iter_mut() {
+                *x = self.bias[o];
+            }
+            for i in 0..self.inputs {
+                let plane = &input[i * size..(i + 1) * size];
+                for ky in 0..k {
+                    for kx in 0..k {
+                        let w = self.weights[((o * self.inputs + i) * k + ky) * k + kx];
+                        if w == 0.0 {
+                            continue;
+                        }
+                        let (dy, dx) = (ky as isize - r, kx as isize - r);
+                        for y in 0..rows {
+                            let yy = y as isize + dy;
+                            if yy < 0 || yy >= rows as isize {
+                                continue;
+                            }
+                            for x in 0..cols {
+                                let xx = x as isize + dx;
+                                if xx >= 0 && xx < cols as isize {
+                                    out[y * cols + x] += w * plane[yy as usize * cols + xx as usize];
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            if relu {
+                for x in out.iter_mut() {
+                    *x = x.max(0.0);
+                }
+            }
+        }
+        res
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Dense {
+    inputs: usize,
+    outputs: usize,
+    weights: Vec<f32>,
+    bias: Vec<f32>,
+}
+
+impl Dense {
+    fn forward(&self, input: &[f32]) -> Vec<f32> {
+        (0..self.outputs).map(|o| {
+            let row = &self.weights[o * self.inputs..(o + 1) * self.inputs];
+            self.bias[o] + row.iter().zip(input).map(|(w, x)| w * x).sum::<f32>()
+        }).collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvNet {
+    hidden: Vec<Conv>,
+    policy: Conv,
+    dense: Dense,
+}
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_f32s<R: Read>(r: &mut R, n: usize) -> io::Result<Vec<f32>> {
+    let mut buf = vec![0; 4 * n];
+    r.read_exact(&mut buf)?;
+    Ok(buf.chunks(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect())
+}
+
+fn write_f32s<W: Write>(w: &mut W, xs: &[f32]) -> io::Result<()> {
+    for x in xs {
+        w.write_all(&x.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl ConvNet {
+    /// A network with small random weights, for the dimensions of `board`.
+    pub fn random<R: Rng>(board: &Board, filters: usize, layers: usize, rng: &mut R) -> Self {
+        let (planes, _, _) = observation_shape(board);
+        let mut random = |inputs: usize, n: usize| {
+            let scale = (2.0 / inputs as f32).sqrt();
+            (0..n).map(|_| rng.gen_range(-scale, scale)).collect::<Vec<_>>()
+        };
+        let hidden = (0..layers).map(|l| {
+            let inputs = if l == 0 { planes } else { filters };
+            Conv { inputs, outputs: filters, kernel: 3, weights: random(9 * inputs, filters * inputs * 9), bias: vec![0.0; filters] }
+        }).collect();
+        let channels = board.piece_count() * ORIENTATIONS;
+        let outputs = board.colors() + 1;
+        ConvNet {
+            hidden,
+            policy: Conv { inputs: filters, outputs: channels, kernel: 1, weights: random(filters, channels * filters), bias: vec![0.0; channels] },
+            dense: Dense { inputs: filters, outputs, weights: random(filters, outputs * filters), bias: vec![0.0; outputs] },
+        }
+    }
+
+    /// Whether the network fits the observations and actions of `board`.
+    pub fn check(&self, board: &Board) -> Result<(), String> {
+        let (planes, _, _) = observation_shape(board);
+        let inputs = self.hidden.first().map_or(self.policy.inputs, |c| c.inputs);
+        if inputs != planes {
+            return Err(format!("the network takes {} input planes, but the board has {}", inputs, planes));
+        }
+        if self.policy.outputs != board.piece_count() * ORIENTATIONS {
+            return Err(format!("the network has {} policy channels, but the board needs {}",
+                               self.policy.outputs, board.piece_count() * ORIENTATIONS));
+        }
+        if self.dense.outputs != board.colors() + 1 {
+            return Err(format!("the network has {} dense outputs, but the board needs {}",
+                               self.dense.outputs, board.colors() + 1));
+        }
+        Ok(())
+    }
+
+    pub fn read<R: Read>(r: &mut R) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a network weights file"));
+        }
+        if read_u32(r)? != VERSION {
+            return Err(invalid("unsupported weights file version"));
+        }
+        let mut header = [0; 5];
+        for x in header.iter_mut() {
+            *x = read_u32(r)? as usize;
+        }
+        let [planes, filters, layers, channels, outputs] = header;
+        if filters == 0 || layers > 1000 || [planes, filters, channels, outputs].iter().any(|&n| n > 1 << 16) {
+            return Err(invalid("implausible network dimensions"));
+        }
+        let mut conv = |inputs: usize, outputs: usize, kernel: usize| -> io::Result<Conv> {
+            let weights = read_f32s(r, outputs * inputs * kernel * kernel)?;
+            let bias = read_f32s(r, outputs)?;
+            Ok(Conv { inputs, outputs, kernel, weights, bias })
+        };
+        let mut hidden = Vec::new();
+        for l in 0..layers {
+            hidden.push(conv(if l == 0 { planes } else { filters }, filters, 3)?);
+        }
+        let policy = conv(if layers == 0 { planes } else { filters }, channels, 1)?;
+        let dense = Dense {
+            inputs: policy.inputs,
+            outputs,
+            weights: read_f32s(r, outputs * policy.inputs)?,
+            bias: read_f32s(r, outputs)?,
+        };
+        Ok(ConvNet { hidden, policy, dense })
+    }
+
+    pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        let planes = self.hidden.first().map_or(self.policy.inputs, |c| c.inputs);
+        let header = [VERSION as usize, planes, self.policy.inputs, self.hidden.len(), self.policy.outputs, self.dense.outputs];
+        for &x in header.iter() {
+            w.write_all(&(x as u32).to_le_bytes())?;
+        }
+        for conv in self.hidden.iter().chain(Some(&self.policy)) {
+            write_f32s(w, &conv.weights)?;
+            write_f32s(w, &conv.bias)?;
+        }
+        write_f32s(w, &self.dense.weights)?;
+        write_f32s(w, &self.dense.bias)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read(&mut BufReader::new(File::open(path)?))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write(&mut w)?;
+        w.flush()
+    }
+}
+
+impl Evaluator for ConvNet {
+    fn evaluate(&mut self, board: &Board, view: &Perspective) -> (Vec<f32>, Vec<f32>) {
+        if let Err(e) = self.check(board) {
+            panic!("{}", e);
+        }
+        let (rows, cols) = (board.rows(), board.cols());
+        let size = rows * cols;
+        let mut x = view.observation(board);
+        for conv in self.hidden.iter() {
+            x = conv.forward(&x, rows, cols, true);
+        }
+        let mut logits = self.policy.forward(&x, rows, cols, false);
+        let pooled = x.chunks(size).map(|plane| plane.iter().sum::<f32>() / size as f32).collect::<Vec<_>>();
+        let out = self.dense.forward(&pooled);
+        let colors = board.colors();
+        logits.push(out[colors]);
+        (logits, out[..colors].iter().map(|v| v.tanh()).collect())
+    }
+
+    fn check(&self, board: &Board) -> Result<(), String> {
+        ConvNet::check(self, board)
+    }
+}
+
+#[test]
+fn test_conv() {
+    // A 3x3 kernel that sums the neighbourhood, on a 2x3 board.
+    let conv = Conv { inputs: 1, outputs: 1, kernel: 3, weights: vec![1.0; 9], bias: vec![0.5] };
+    let input = [1.0, 0.0, 0.0, 0.0, 0.0, 2.0];
+    assert_eq!(conv.forward(&input, 2, 3, false), vec![1.5, 3.5, 2.5, 1.5, 3.5, 2.5]);
+    let negate = Conv { inputs: 1, outputs: 1, kernel: 1, weights: vec![-1.0], bias: vec![0.0] };
+    assert_eq!(negate.forward(&input, 2, 3, true), vec![0.0; 6]);
+}
+
+#[test]
+fn test_weights_file_and_evaluation() {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    let board = ::game::Game::standard(7, 2, 3).unwrap().board().clone();
+    let mut net = ConvNet::random(&board, 4, 2, &mut StdRng::seed_from_u64(1));
+    let mut bytes = Vec::new();
+    net.write(&mut bytes).unwrap();
+    let mut read = ConvNet::read(&mut &bytes[..]).unwrap();
+    assert_eq!(read, net);
+    assert!(ConvNet::read(&mut &bytes[..bytes.len() - 1]).is_err());
+    assert!(ConvNet::read(&mut &b"nope"[..]).is_err());
+
+    let view = Perspective::new(&board, 0, true);
+    let (logits, values) = read.evaluate(&board, &view);
+    assert_eq!(logits.len(), ::encoding::action_count(&board));
+    assert_eq!(values.len(), 2);
+    assert!(values.iter().all(|v| v.abs() <= 1.0));
+    assert_eq!(net.evaluate(&board, &view), (logits, values));
+    let bigger = ::game::Game::standard(7, 4, 3).unwrap().board().clone();
+    assert!(net.check(&bigger).is_err());
+}
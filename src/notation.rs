@@ -0,0 +1,138 @@
+//! Text notation for moves and games.
+//!
+//! A cell is written as a column letter followed by a 1-based row number,
+//! e.g. `a1` for the top left cell; columns after `z` continue with `aa`, `ab`, ...
+//! A move is the list of cells it covers, sorted and separated by commas,
+//! e.g. `a1,a2,b2`, and a pass is written `pass`.
+//! The cells determine the shape of the piece. Per-color piece sets may hold
+//! several pieces of the same shape, so `parse_move` takes the first piece of
+//! that shape the color still has in stock, and otherwise the first piece of
+//! that shape, which `Board::check_move` then rejects as used.
+
+use board::{Board, Color, Move};
+
+pub const PASS: &str = "pass";
+
+pub fn format_cell(board: &Board, i: usize) -> String {
+    let (mut x, y) = (i % board.cols(), i / board.cols());
+    let mut column = Vec::new();
+    loop {
+        column.push(b'a' + (x % 26) as u8);
+        if x < 26 {
+            break;
+        }
+        x = x / 26 - 1;
+    }
+    column.reverse();
+    format!("{}{}", String::from_utf8(column).unwrap(), y + 1)
+}
+
+pub fn parse_cell(board: &Board, s: &str) -> Result<usize, String> {
+    let split = s.find(|c: char| !c.is_ascii_lowercase()).unwrap_or(s.len());
+    let (column, row) = s.split_at(split);
+    if column.is_empty() {
+        return Err(format!("missing column in cell {:?}", s));
+    }
+    if column.len() > 4 {
+        return Err(format!("cell {:?} is outside the board", s));
+    }
+    let x = column.bytes().fold(0, |x, c| x * 26 + (c - b'a' + 1) as usize) - 1;
+    let y = match row.parse::<usize>() {
+        Ok(y) if y >= 1 => y - 1,
+        _ => return Err(format!("invalid row in cell {:?}", s)),
+    };
+    if x >= board.cols() || y >= board.rows() {
+        return Err(format!("cell {:?} is outside the board", s));
+    }
+    Ok(x + y * board.cols())
+}
+
+pub fn format_move(board: &Board, mv: Option<Move>) -> String {
+    match mv {
+        None => PASS.to_string(),
+        Some(mv) => {
+            let mut cells = board.cells(mv);
+            cells.sort_unstable();
+            cells.iter().map(|&i| format_cell(board, i)).collect::<Vec<_>>().join(",")
+        },
+    }
+}
+
+/// Parse a move of `color`, checking that it is legal in the current position
+/// and otherwise saying why not.
+pub fn parse_move(board: &Board, color: Color, s: &str) -> Result<Option<Move>, String> {
+    let s = s.trim();
+    if s == PASS {
+        return Ok(None);
+    }
+    let cells = s.split(',').map(|c| parse_cell(board, c.trim())).collect::<Result<Vec<_>, _>>()?;
+    // Several pieces may have the shape; prefer one the color still has.
+    let shapes = (0..board.piece_count()).filter_map(|piece| board.find_move(piece, &cells)).collect::<Vec<_>>();
+    let &(piece, variation, anchor) = shapes.iter().find(|&&(piece, _, _)| board.has_piece(color, piece))
+        .or_else(|| shapes.first())
+        .ok_or_else(|| format!("{} is not the shape of a piece", s))?;
+    board.check_move(color, piece, variation, anchor)
+        .map(Some)
+        .map_err(|e| format!("{} is not a legal move for color {}: {}", s, color, e))
+}
+
+/// A finished game with the names of the players in each seat,
+/// written with PGN-style tags followed by one move per line.
+pub fn format_record(board: &Board, tags: &[(String, String)], moves: &[Option<Move>]) -> String {
+    let mut res = String::new();
+    for (key, value) in tags {
+        res.push_str(&format!("[{} {:?}]\n", key, value));
+    }
+    for (i, &mv) in moves.iter().enumerate() {
+        res.push_str(&format!("{}. {} {}\n", i / board.colors() + 1, i % board.colors(), format_move(board, mv)));
+    }
+    res
+}
+
+#[cfg(test)]
+fn test_board(rows: usize, cols: usize) -> Board {
+    Board::new(::pieces::compute_pieces(3), rows, cols, &[(0, 0), (cols as i8 - 1, rows as i8 - 1)]).unwrap()
+}
+
+#[test]
+fn test_cells() {
+    let b = test_board(3, 60);
+    for i in 0..b.size() {
+        assert_eq!(parse_cell(&b, &format_cell(&b, i)), Ok(i));
+    }
+    assert_eq!(format_cell(&b, 0), "a1");
+    assert_eq!(format_cell(&b, 25 + 60), "z2");
+    assert_eq!(format_cell(&b, 26), "aa1");
+    assert_eq!(format_cell(&b, 52), "ba1");
+    assert!(parse_cell(&b, "a0").is_err());
+    assert!(parse_cell(&b, "a4").is_err());
+    assert!(parse_cell(&b, "3").is_err());
+    assert!(parse_cell(&b, "ca1").is_err());
+}
+
+#[test]
+fn test_moves() {
+    let mut b = test_board(5, 5);
+    let legal = b.legal_moves(0).unwrap();
+    for &mv in legal.iter() {
+        assert_eq!(parse_move(&b, 0, &format_move(&b, Some(mv))), Ok(Some(mv)));
+    }
+    assert_eq!(parse_move(&b, 0, "pass"), Ok(None));
+    assert_eq!(parse_move(&b, 0, " b1, a1 ").map(|m| format_move(&b, m)), Ok("a1,b1".to_string()));
+    assert_eq!(parse_move(&b, 0, "b1,c1"),
+               Err("b1,c1 is not a legal move for color 0: the first piece must cover the home cell 0".to_string()));
+    assert_eq!(parse_move(&b, 0, "a1,c1"), Err("a1,c1 is not the shape of a piece".to_string()));
+    b.place(0, legal[0]).unwrap();
+    assert!(parse_move(&b, 0, &format_move(&b, Some(legal[0]))).is_err());
+}
+
+#[test]
+fn test_duplicate_shapes() {
+    // Pieces 0 and 2 are both monominoes, and color 0 only has the second.
+    let mut pieces = ::pieces::compute_pieces(2);
+    pieces.extend(::pieces::compute_pieces(1));
+    let b = Board::new(pieces, 3, 3, &[(0, 0), (2, 2)]).unwrap()
+        .with_piece_sets(vec![vec![0, 1, 1], vec![1, 1, 0]]).unwrap();
+    assert_eq!(parse_move(&b, 0, "a1").map(|m| m.map(|(p, _, _)| p)), Ok(Some(2)));
+    assert_eq!(parse_move(&b, 1, "c3").map(|m| m.map(|(p, _, _)| p)), Ok(Some(0)));
+}
@@ -0,0 +1,368 @@
+use std::fmt;
+
+use board::{Board, Color};
+
+/// Statistics of a position that objectives are computed from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Regions {
+    pub occupied: usize,
+    /// `area[c]` is the number of cells occupied by color `c`.
+    pub area: Vec<usize>,
+    /// Number of empty regions when diagonally touching cells are connected.
+    pub eightway: usize,
+    /// Sizes of the empty regions when only edge-sharing cells are connected.
+    pub cardinal: Vec<usize>,
+}
+
+impl Regions {
+    pub fn new(board: &Board) -> Self {
+        let mut area = vec![0; board.colors()];
+        for i in 0..board.size() {
+            if let Some(c) = board.at(i) {
+                area[c as usize] += 1;
+            }
+        }
+        let mut cardinal = Vec::new();
+        let mut eightway = 0;
+        let mut cardinal_seen = vec![false; board.size()];
+        let mut eightway_seen = vec![false; board.size()];
+        let mut stack = Vec::new();
+        for start in 0..board.size() {
+            if !board.is_vacant(start) {
+                continue;
+            }
+            if !cardinal_seen[start] {
+                let mut size = 0;
+                stack.push(start);
+                while let Some(i) = stack.pop() {
+                    if cardinal_seen[i] {
+                        continue;
+                    }
+                    cardinal_seen[i] = true;
+                    size += 1;
+                    stack.extend(board.cardinal_neighbors(i).filter(|&j| board.is_vacant(j) && !cardinal_seen[j]));
+                }
+                cardinal.push(size);
+            }
+            if !eightway_seen[start] {
+                eightway += 1;
+                stack.push(start);
+                while let Some(i) = stack.pop() {
+                    if eightway_seen[i] {
+                        continue;
+                    }
+                    eightway_seen[i] = true;
+                    stack.extend(board.cardinal_neighbors(i).chain(board.diagonal_neighbors(i))
+                                 .filter(|&j| board.is_vacant(j) && !eightway_seen[j]));
+                }
+            }
+        }
+        Regions {
+            occupied: area.iter().sum(),
+            area,
+            eightway,
+            cardinal,
+        }
+    }
+}
+
+impl fmt::Display for Regions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "occupied = {}, eightway = {}, cardinal = {}", self.occupied, self.eightway, self.cardinal.len())
+    }
+}
+
+/// A quantity computed from `Regions`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Term {
+    /// Number of occupied cells.
+    Occupied,
+    /// Number of 8-connected empty regions.
+    Eightway,
+    /// Number of 4-connected empty regions.
+    Cardinal,
+    /// Number of 4-connected empty regions with fewer than `k` cells.
+    Holes(usize),
+    /// Number of empty cells with no empty cell next to them.
+    Isolated,
+    /// Size of the largest 4-connected empty region.
+    Largest,
+    /// Number of cells occupied by the given color.
+    Area(Color),
+}
+
+impl Term {
+    pub fn compute(self, regions: &Regions) -> f64 {
+        (match self {
+            Term::Occupied => regions.occupied,
+            Term::Eightway => regions.eightway,
+            Term::Cardinal => regions.cardinal.len(),
+            Term::Holes(k) => regions.cardinal.iter().filter(|&&s| s < k).count(),
+            Term::Isolated => regions.cardinal.iter().filter(|&&s| s == 1).count(),
+            Term::Largest => regions.cardinal.iter().cloned().max().unwrap_or(0),
+            Term::Area(c) => regions.area.get(c as usize).cloned().unwrap_or(0),
+        }) as f64
+    }
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Term::Occupied => write!(f, "occupied"),
+            Term::Eightway => write!(f, "eightway"),
+            Term::Cardinal => write!(f, "cardinal"),
+            Term::Holes(k) => write!(f, "holes({})", k),
+            Term::Isolated => write!(f, "isolated"),
+            Term::Largest => write!(f, "largest"),
+            Term::Area(c) => write!(f, "area({})", c),
+        }
+    }
+}
+
+/// An arithmetic expression over `Term`s, e.g. `occupied - 3*eightway - cardinal`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Objective {
+    Const(f64),
+    Term(Term),
+    Neg(Box<Objective>),
+    Add(Box<Objective>, Box<Objective>),
+    Sub(Box<Objective>, Box<Objective>),
+    Mul(Box<Objective>, Box<Objective>),
+}
+
+pub const DEFAULT_OBJECTIVE: &str = "occupied - 3*eightway - cardinal";
+
+impl Default for Objective {
+    fn default() -> Self {
+        Objective::parse(DEFAULT_OBJECTIVE).unwrap()
+    }
+}
+
+impl Objective {
+    pub fn evaluate(&self, regions: &Regions) -> f64 {
+        match *self {
+            Objective::Const(x) => x,
+            Objective::Term(t) => t.compute(regions),
+            Objective::Neg(ref a) => -a.evaluate(regions),
+            Objective::Add(ref a, ref b) => a.evaluate(regions) + b.evaluate(regions),
+            Objective::Sub(ref a, ref b) => a.evaluate(regions) - b.evaluate(regions),
+            Objective::Mul(ref a, ref b) => a.evaluate(regions) * b.evaluate(regions),
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut p = Parser { s: s.as_bytes(), i: 0 };
+        let res = p.expr()?;
+        p.skip_whitespace();
+        if p.i < p.s.len() {
+            return Err(p.error("unexpected character"));
+        }
+        Ok(res)
+    }
+}
+
+impl fmt::Display for Objective {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Objective::Const(x) => write!(f, "{}", x),
+            Objective::Term(t) => write!(f, "{}", t),
+            Objective::Neg(ref a) => write!(f, "-({})", a),
+            Objective::Add(ref a, ref b) => write!(f, "{} + {}", a, b),
+            Objective::Sub(ref a, ref b) => match **b {
+                Objective::Add(..) | Objective::Sub(..) => write!(f, "{} - ({})", a, b),
+                _ => write!(f, "{} - {}", a, b),
+            },
+            Objective::Mul(ref a, ref b) => {
+                let paren = |o: &Objective| match *o {
+                    Objective::Add(..) | Objective::Sub(..) => format!("({})", o),
+                    _ => format!("{}", o),
+                };
+                write!(f, "{}*{}", paren(a), paren(b))
+            },
+        }
+    }
+}
+
+/// Recursive descent parser for `Objective`:
+///
+/// ```text
+/// expr   := term (('+' | '-') term)*
+/// term   := factor ('*' factor)*
+/// factor := NUMBER | NAME | NAME '(' NUMBER ')' | '(' expr ')' | '-' factor
+/// ```
+struct Parser<'a> {
+    s: &'a [u8],
+    i: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, msg: &str) -> String {
+        format!("{} at position {} in objective {:?}", msg, self.i, String::from_utf8_lossy(self.s))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.i < self.s.len() && self.s[self.i].is_ascii_whitespace() {
+            self.i += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.s.get(self.i).cloned()
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        if self.peek() == Some(c) {
+            self.i += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn take_while<F: Fn(u8) -> bool>(&mut self, f: F) -> &'a str {
+        let start = self.i;
+        while self.i < self.s.len() && f(self.s[self.i]) {
+            self.i += 1;
+        }
+        ::std::str::from_utf8(&self.s[start..self.i]).unwrap()
+    }
+
+    fn number(&mut self) -> Result<f64, String> {
+        self.skip_whitespace();
+        let digits = self.take_while(|c| c.is_ascii_digit() || c == b'.');
+        digits.parse().map_err(|_| self.error("expected a number"))
+    }
+
+    fn expr(&mut self) -> Result<Objective, String> {
+        let mut res = self.term()?;
+        loop {
+            if self.eat(b'+') {
+                res = Objective::Add(Box::new(res), Box::new(self.term()?));
+            } else if self.eat(b'-') {
+                res = Objective::Sub(Box::new(res), Box::new(self.term()?));
+            } else {
+                return Ok(res);
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<Objective, String> {
+        let mut res = self.factor()?;
+        while self.eat(b'*') {
+            res = Objective::Mul(Box::new(res), Box::new(self.factor()?));
+        }
+        Ok(res)
+    }
+
+    fn factor(&mut self) -> Result<Objective, String> {
+        match self.peek() {
+            Some(b'-') => {
+                self.i += 1;
+                Ok(Objective::Neg(Box::new(self.factor()?)))
+            },
+            Some(b'(') => {
+                self.i += 1;
+                let res = self.expr()?;
+                if !self.eat(b')') {
+                    return Err(self.error("expected ')'"));
+                }
+                Ok(res)
+            },
+            Some(c) if c.is_ascii_digit() || c == b'.' => Ok(Objective::Const(self.number()?)),
+            Some(c) if c.is_ascii_alphabetic() => {
+                let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == b'_');
+                let term = match name {
+                    "occupied" => Term::Occupied,
+                    "eightway" => Term::Eightway,
+                    "cardinal" => Term::Cardinal,
+                    "isolated" => Term::Isolated,
+                    "largest" => Term::Largest,
+                    "holes" | "area" => {
+                        if !self.eat(b'(') {
+                            return Err(self.error("expected '('"));
+                        }
+                        let arg = self.number()?;
+                        if !self.eat(b')') {
+                            return Err(self.error("expected ')'"));
+                        }
+                        if arg < 0.0 || arg.fract() != 0.0 || (name == "area" && arg > Color::MAX as f64) {
+                            return Err(self.error("invalid argument"));
+                        }
+                        if name == "holes" { Term::Holes(arg as usize) } else { Term::Area(arg as Color) }
+                    },
+                    _ => {
+                        self.i -= name.len();
+                        return Err(self.error(&format!("unknown term {:?}", name)));
+                    },
+                };
+                Ok(Objective::Term(term))
+            },
+            _ => Err(self.error("expected a term")),
+        }
+    }
+}
+
+#[test]
+fn test_parse_objective() {
+    let o = Objective::parse(DEFAULT_OBJECTIVE).unwrap();
+    assert_eq!(o.to_string(), "occupied - 3*eightway - cardinal");
+    let o = Objective::parse("2 * (area(0) - holes(3)) + -largest").unwrap();
+    assert_eq!(o.to_string(), "2*(area(0) - holes(3)) + -(largest)");
+    assert_eq!(Objective::parse(&o.to_string()).unwrap(), o);
+    assert!(Objective::parse("occupied +").is_err());
+    assert!(Objective::parse("holes").is_err());
+    assert!(Objective::parse("holes(1.5)").is_err());
+    assert!(Objective::parse("area(300)").is_err());
+    assert!(Objective::parse("ocupied").is_err());
+    assert!(Objective::parse("(occupied").is_err());
+    assert!(Objective::parse("occupied)").is_err());
+}
+
+#[test]
+fn test_regions() {
+    let pieces = ::pieces::compute_pieces(2);
+    let mut b = Board::new(pieces, 3, 3, &[(0, 0), (2, 2)]).unwrap();
+    // Vertical domino for color 0 and horizontal domino for color 1:
+    // ▓ . .
+    // ▓ . .
+    // . ▓ ▓
+    let mv = b.legal_moves(0).unwrap().into_iter().find(|&(p, v, _)| p == 1 && v == 0).unwrap();
+    b.place(0, mv).unwrap();
+    let mv = b.legal_moves(1).unwrap().into_iter().find(|&(p, v, _)| p == 1 && v == 1).unwrap();
+    b.place(1, mv).unwrap();
+    let r = Regions::new(&b);
+    assert_eq!(r.occupied, 4);
+    assert_eq!(r.area, vec![2, 2]);
+    assert_eq!(r.eightway, 1);
+    let mut cardinal = r.cardinal.clone();
+    cardinal.sort();
+    assert_eq!(cardinal, vec![1, 4]);
+    let value = |s: &str| Objective::parse(s).unwrap().evaluate(&r);
+    assert_eq!(value(DEFAULT_OBJECTIVE), 4.0 - 3.0 - 2.0);
+    assert_eq!(value("isolated"), 1.0);
+    assert_eq!(value("holes(2)"), 1.0);
+    assert_eq!(value("holes(5)"), 2.0);
+    assert_eq!(value("largest"), 4.0);
+    assert_eq!(value("area(1) * 0.5"), 1.0);
+}
+
+#[test]
+fn test_regions_with_terrain() {
+    // The neutral cell splits the row into two regions, and the hole is no region.
+    let b = Board::from_map(::pieces::compute_pieces(2), "0..#..1\n  ___  ").unwrap();
+    let r = Regions::new(&b);
+    assert_eq!(r.occupied, 0);
+    assert_eq!(r.eightway, 2);
+    assert_eq!(r.cardinal, vec![3, 3]);
+}
+
+#[test]
+fn test_regions_on_cylinder() {
+    // The neutral column splits the plane but not the cylinder.
+    let map = "0#..\n.#..\n.#.1";
+    let b = Board::from_map(::pieces::compute_pieces(2), map).unwrap();
+    assert_eq!(Regions::new(&b).cardinal.len(), 2);
+    let b = b.with_topology(::neighbors::Topology::CYLINDER).unwrap();
+    assert_eq!(Regions::new(&b).cardinal, vec![9]);
+}
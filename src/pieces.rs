@@ -53,7 +53,7 @@ impl Piece {
         self.map(|(x, y)| (-y, x))
     }
 
-    fn vary<F>(&self, mut f: F) where F: FnMut(Piece) -> () {
+    fn vary<F>(&self, mut f: F) where F: FnMut(Piece) {
         f(self.clone().translate_origin());
         f(self.clone().rot_ccw().translate_origin());
         f(self.clone().rot_ccw().rot_ccw().translate_origin());
@@ -73,12 +73,12 @@ impl Piece {
     }
 
     fn canonical(&self) -> Piece {
-        let mut res = vec![self.clone().translate_origin()];
-        self.vary(|p| {let r = res[0].clone().min(p.sorted()); res[0] = r;});
-        res[0].clone()
+        let mut res = self.clone().translate_origin();
+        self.vary(|p| {res = res.clone().min(p.sorted());});
+        res
     }
 
-    fn expand<F>(&self, mut f: F) where F: FnMut(Piece) -> () {
+    fn expand<F>(&self, mut f: F) where F: FnMut(Piece) {
         let mut try = |p| {
             if !self.points.contains(&p) {
                 let mut ps = self.points.clone();
@@ -128,9 +128,9 @@ pub fn print_pieces(pieces: &[Piece]) {
                     print!(" ");
                 }
             }
-            println!("");
+            println!();
         }
-        println!("");
+        println!();
     }
 }
 
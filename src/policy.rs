@@ -0,0 +1,95 @@
+use rand::{Rng, RngCore};
+
+use board::{Board, Color, Move, Moves};
+use treesearch::{GameStateMut, PlayoutPolicy, Uniform};
+
+/// A game state backed by a `Board`, giving policies access to the move list.
+pub trait BoardState: GameStateMut {
+    /// The moves that `select_move` indexes into.
+    fn moves(&mut self) -> Moves<'_>;
+}
+
+/// Scores a single move; higher is better.
+pub trait MoveHeuristic {
+    fn score(&self, board: &Board, color: Color, mv: Move) -> f64;
+}
+
+/// Prefers placing the biggest remaining pieces.
+pub struct PieceSize;
+
+impl MoveHeuristic for PieceSize {
+    fn score(&self, board: &Board, _color: Color, (piece, _, _): Move) -> f64 {
+        board.piece_size(piece) as f64
+    }
+}
+
+/// Prefers moves that open up the most new corners.
+pub struct CornerGain;
+
+impl MoveHeuristic for CornerGain {
+    fn score(&self, board: &Board, color: Color, mv: Move) -> f64 {
+        board.corner_gain(color, mv) as f64
+    }
+}
+
+/// Plays a uniformly random move with probability `epsilon`,
+/// and otherwise a random move among those with the highest heuristic score.
+pub struct EpsilonGreedy<H> {
+    pub heuristic: H,
+    pub epsilon: f64,
+}
+
+impl<H: MoveHeuristic> EpsilonGreedy<H> {
+    pub fn new(heuristic: H, epsilon: f64) -> Self {
+        EpsilonGreedy { heuristic, epsilon }
+    }
+}
+
+impl<G: BoardState, H: MoveHeuristic> PlayoutPolicy<G> for EpsilonGreedy<H> {
+    fn choose(&mut self, game_state: &mut G, count: usize, rng: &mut dyn RngCore) -> usize {
+        if self.epsilon > 0.0 && rng.gen::<f64>() < self.epsilon {
+            return rng.gen_range(0, count);
+        }
+        let moves = game_state.moves();
+        let mut best = Vec::new();
+        let mut best_score = f64::NEG_INFINITY;
+        for (i, &mv) in moves.moves.iter().enumerate() {
+            let score = self.heuristic.score(moves.board(), moves.color(), mv);
+            if score > best_score {
+                best_score = score;
+                best.clear();
+            }
+            if score >= best_score {
+                best.push(i);
+            }
+        }
+        best[rng.gen_range(0, best.len())]
+    }
+}
+
+/// Parse a policy name as given on the command line:
+/// `uniform`, `biggest` or `corners`, the latter two optionally
+/// followed by `:EPSILON` to play uniformly at random with that probability.
+pub fn parse_policy<G: BoardState + 'static>(spec: &str) -> Result<Box<dyn PlayoutPolicy<G>>, String> {
+    let mut parts = spec.splitn(2, ':');
+    let name = parts.next().unwrap();
+    let epsilon = parts.next();
+    if name == "uniform" {
+        return match epsilon {
+            None => Ok(Box::new(Uniform)),
+            Some(_) => Err(format!("policy \"uniform\" takes no epsilon, got {:?}", spec)),
+        };
+    }
+    let epsilon = match epsilon {
+        Some(e) => match e.parse::<f64>() {
+            Ok(e) if (0.0..=1.0).contains(&e) => e,
+            _ => return Err(format!("invalid epsilon in policy {:?}", spec)),
+        },
+        None => 0.0,
+    };
+    match name {
+        "biggest" => Ok(Box::new(EpsilonGreedy::new(PieceSize, epsilon))),
+        "corners" => Ok(Box::new(EpsilonGreedy::new(CornerGain, epsilon))),
+        _ => Err(format!("unknown policy {:?}", spec)),
+    }
+}
@@ -0,0 +1,348 @@
+//! A line-based text protocol for driving the engine from another program,
+//! modelled on GTP: every command is answered with `= RESULT` or `? ERROR`
+//! followed by an empty line. Moves are written in the notation of `notation`.
+//!
+//! Commands:
+//!
+//! ```text
+//! name / version / protocol_version / list_commands / quit
+//! boardsize COLS [ROWS]     new empty board of the given size
+//! homes CELL CELL ...       one home cell per color, e.g. `homes a1 t20`
+//! pieces MAX_SIZE           play with all pieces of up to MAX_SIZE squares
+//! clear_board               start a new game with the current setup
+//! play [COLOR] MOVE         play a move for the color to move
+//! legal_moves               list the legal moves of the color to move
+//! undo                      take back the last move
+//! genmove [MILLISECONDS]    let the engine choose and play a move, with the time
+//!                           limit if the agent has one
+//! agent SPEC                set the agent used by genmove, see `agent::parse_agent`
+//! showboard / to_move / scores
+//! ```
+
+use std::io::{BufRead, Write};
+use std::mem;
+use std::time::Duration;
+
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+use agent::{Agent, parse_agent};
+use board::{Board, Color, Terrain};
+use game::Game;
+use notation::{format_move, parse_cell, parse_move};
+use pieces::{Point, compute_pieces};
+use treesearch::GameStateMut;
+
+pub const PROTOCOL_VERSION: &str = "1";
+
+const COMMANDS: &[&str] = &[
+    "agent", "boardsize", "clear_board", "genmove", "homes", "legal_moves", "list_commands", "name",
+    "pieces", "play", "protocol_version", "quit", "scores", "showboard", "to_move", "undo", "version",
+];
+
+pub struct Engine {
+    cols: usize,
+    rows: usize,
+    homes: Vec<Point>,
+    piece_size: usize,
+    game: Game,
+    agent: Box<dyn Agent>,
+    rng: StdRng,
+}
+
+fn corners(cols: usize, rows: usize) -> Vec<Point> {
+    let (w, h) = (cols as i8 - 1, rows as i8 - 1);
+    vec![(0, 0), (w, 0), (w, h), (0, h)]
+}
+
+/// Plain text picture of the board, drawn like the maps of `Board::from_map`: `.` for
+/// empty cells, `#` for neutral cells, a space for holes and otherwise the color
+/// number as a base-36 digit, or `*` for colors beyond `z`.
+pub fn board_to_text(board: &Board) -> String {
+    let mut res = String::new();
+    for y in 0..board.rows() {
+        for x in 0..board.cols() {
+            let i = x + y * board.cols();
+            match (board.terrain(i), board.at(i)) {
+                (Terrain::Hole, _) => res.push(' '),
+                (Terrain::Neutral, _) => res.push('#'),
+                (Terrain::Open, None) => res.push('.'),
+                (Terrain::Open, Some(c)) => res.push(std::char::from_digit(c as u32, 36).unwrap_or('*')),
+            }
+        }
+        res.push('\n');
+    }
+    res
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine::new(StdRng::seed_from_u64(0))
+    }
+}
+
+impl Engine {
+    /// A standard four-player game on a 20x20 board.
+    pub fn new(rng: StdRng) -> Self {
+        let (cols, rows, piece_size) = (20, 20, 5);
+        let homes = corners(cols, rows);
+        Engine {
+            cols,
+            rows,
+            game: Game::new(Board::new(compute_pieces(piece_size), rows, cols, &homes).unwrap()),
+            homes,
+            piece_size,
+            agent: parse_agent("greedy").unwrap(),
+            rng,
+        }
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// Start a new game with the given setup, which is kept only if the board
+    /// can be created; otherwise the engine is left as it was.
+    fn set_up(&mut self, cols: usize, rows: usize, homes: Vec<Point>, piece_size: usize) -> Result<(), String> {
+        let board = Board::new(compute_pieces(piece_size), rows, cols, &homes).map_err(|e| e.to_string())?;
+        self.game = Game::new(board);
+        self.cols = cols;
+        self.rows = rows;
+        self.homes = homes;
+        self.piece_size = piece_size;
+        Ok(())
+    }
+
+    fn clear_board(&mut self) -> Result<(), String> {
+        self.set_up(self.cols, self.rows, self.homes.clone(), self.piece_size)
+    }
+
+    /// Handle one command line; `Ok(None)` means the session should end.
+    pub fn execute(&mut self, line: &str) -> Result<Option<String>, String> {
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(c) => c,
+            None => return Ok(Some(String::new())),
+        };
+        let args = words.collect::<Vec<_>>();
+        let no_args = |res: String| if args.is_empty() { Ok(Some(res)) } else { Err("too many arguments".to_string()) };
+        match command {
+            "name" => no_args("blokus".to_string()),
+            "version" => no_args(env!("CARGO_PKG_VERSION").to_string()),
+            "protocol_version" => no_args(PROTOCOL_VERSION.to_string()),
+            "list_commands" => no_args(COMMANDS.join("\n")),
+            "quit" => Ok(None),
+            "boardsize" => {
+                let size = args.iter().map(|a| a.parse::<usize>()).collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| "expected a number".to_string())?;
+                let (cols, rows) = match size.len() {
+                    1 => (size[0], size[0]),
+                    2 => (size[0], size[1]),
+                    _ => return Err("expected COLS [ROWS]".to_string()),
+                };
+                if cols < 1 || rows < 1 || cols > 100 || rows > 100 {
+                    return Err("unacceptable size".to_string());
+                }
+                self.set_up(cols, rows, corners(cols, rows), self.piece_size)?;
+                Ok(Some(String::new()))
+            },
+            "homes" => {
+                if args.is_empty() {
+                    return Err("expected a cell for each color".to_string());
+                }
+                let homes = args.iter().map(|a| parse_cell(self.game.board(), a)).collect::<Result<Vec<_>, _>>()?;
+                let cols = self.game.board().cols();
+                let homes = homes.iter().map(|&i| ((i % cols) as i8, (i / cols) as i8)).collect();
+                self.set_up(self.cols, self.rows, homes, self.piece_size)?;
+                Ok(Some(String::new()))
+            },
+            "pieces" => {
+                let piece_size = match args.first().map(|a| a.parse::<usize>()) {
+                    Some(Ok(n)) if args.len() == 1 && (1..=6).contains(&n) => n,
+                    _ => return Err("expected a piece size between 1 and 6".to_string()),
+                };
+                self.set_up(self.cols, self.rows, self.homes.clone(), piece_size)?;
+                Ok(Some(String::new()))
+            },
+            "clear_board" => {
+                self.clear_board()?;
+                no_args(String::new())
+            },
+            "play" => {
+                let color = self.game.to_move();
+                let text = match args.len() {
+                    1 => args[0],
+                    2 => {
+                        if args[0].parse::<Color>().ok() != Some(color) {
+                            return Err(format!("it is the turn of color {}", color));
+                        }
+                        args[1]
+                    },
+                    _ => return Err("expected [COLOR] MOVE".to_string()),
+                };
+                let mv = parse_move(self.game.board(), color, text)?;
+                self.game.play(mv).map_err(|e| e.to_string())?;
+                Ok(Some(String::new()))
+            },
+            "legal_moves" => {
+                if self.game.move_count() == 0 {
+                    return no_args(String::new());
+                }
+                let moves = if self.game.must_pass() { vec![None] } else { self.game.legal_moves().iter().map(|&m| Some(m)).collect() };
+                no_args(moves.into_iter().map(|m| format_move(self.game.board(), m)).collect::<Vec<_>>().join("\n"))
+            },
+            "undo" => {
+                if !args.is_empty() {
+                    return Err("too many arguments".to_string());
+                }
+                self.game.take_back().ok_or_else(|| "nothing to undo".to_string())?;
+                Ok(Some(String::new()))
+            },
+            "genmove" => {
+                if self.game.move_count() == 0 {
+                    return Err("the game is over".to_string());
+                }
+                self.agent.check(self.game.board())?;
+                let mv = match args.len() {
+                    0 => self.agent.choose(&mut self.game, &mut self.rng),
+                    1 => {
+                        let ms = args[0].parse::<u64>().map_err(|_| "expected milliseconds".to_string())?;
+                        let name = self.agent.name();
+                        let limit = self.agent.time_limit().ok_or_else(|| format!("agent {} has no time limit", name))?;
+                        let previous = mem::replace(limit, Duration::from_millis(ms));
+                        let mv = self.agent.choose(&mut self.game, &mut self.rng);
+                        *self.agent.time_limit().unwrap() = previous;
+                        mv
+                    },
+                    _ => return Err("too many arguments".to_string()),
+                };
+                let text = format_move(self.game.board(), mv);
+                self.game.play(mv).map_err(|e| e.to_string())?;
+                Ok(Some(text))
+            },
+            "agent" => {
+                if args.len() != 1 {
+                    return Err("expected an agent".to_string());
+                }
+                let agent = parse_agent(args[0])?;
+                agent.check(self.game.board())?;
+                self.agent = agent;
+                Ok(Some(String::new()))
+            },
+            "showboard" => no_args(format!("\n{}", board_to_text(self.game.board()).trim_end())),
+            "to_move" => no_args(if self.game.move_count() == 0 { "none".to_string() } else { self.game.to_move().to_string() }),
+            "scores" => no_args(self.game.scores().iter().map(|s| s.to_string()).collect::<Vec<_>>().join(" ")),
+            _ => Err(format!("unknown command {:?}", command)),
+        }
+    }
+
+    /// Answer commands from `input` until `quit` or the end of input.
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> ::std::io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            match self.execute(line) {
+                Ok(Some(res)) => write!(output, "= {}\n\n", res)?,
+                Ok(None) => {
+                    write!(output, "= \n\n")?;
+                    output.flush()?;
+                    return Ok(());
+                },
+                Err(e) => write!(output, "? {}\n\n", e)?,
+            }
+            output.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+fn session(script: &str) -> Vec<String> {
+    let mut engine = Engine::default();
+    let mut output = Vec::new();
+    engine.run(script.as_bytes(), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    output.split("\n\n").filter(|r| !r.is_empty()).map(|r| r.to_string()).collect()
+}
+
+#[test]
+fn test_setup_and_play() {
+    let responses = session("
+        boardsize 5
+        homes a1 e5
+        pieces 2
+        play a1,b1
+        play 1 e5
+        play a2  # touches the domino along an edge
+        play 1 c2
+        showboard
+        to_move
+        undo
+        to_move
+        quit
+        name
+    ");
+    assert_eq!(responses, vec![
+        "= ", "= ", "= ", "= ", "= ",
+        "? a2 is not a legal move for color 0: cell 5 is next to a piece of the same color",
+        "? it is the turn of color 0",
+        "= \n00...\n.....\n.....\n.....\n....1",
+        "= 0",
+        "= ",
+        "= 1",
+        "= ",
+    ]);
+}
+
+#[test]
+fn test_errors() {
+    let responses = session("foo\nundo\nboardsize x\npieces 9\nhomes z9\nplay\nagent nobody\nname extra");
+    assert!(responses.iter().all(|r| r.starts_with("? ")), "{:?}", responses);
+    assert_eq!(responses.len(), 8);
+
+    // A setup that fails leaves the previous one in place.
+    let mut engine = Engine::default();
+    for line in &["boardsize 6", "homes a1 f6", "pieces 3"] {
+        assert_eq!(engine.execute(line), Ok(Some(String::new())));
+    }
+    // A 1x1 board has the same cell in all four corners.
+    assert!(engine.execute("boardsize 1").is_err());
+    assert!(engine.execute("homes a1 a1").is_err());
+    assert_eq!((engine.cols, engine.rows, engine.homes.clone(), engine.piece_size), (6, 6, vec![(0, 0), (5, 5)], 3));
+    assert_eq!(engine.game().board().size(), 36);
+    assert_eq!(engine.execute("homes b2 e5"), Ok(Some(String::new())));
+    assert_eq!(engine.homes, vec![(1, 1), (4, 4)]);
+}
+
+/// A scripted stand-in for a GUI that lets the engine play itself
+/// and checks every reply against the legal moves.
+#[test]
+fn test_selfplay_through_protocol() {
+    let mut engine = Engine::default();
+    for line in &["boardsize 6", "homes a1 f6", "pieces 3", "agent random"] {
+        assert_eq!(engine.execute(line), Ok(Some(String::new())));
+    }
+    let mut plies = 0;
+    while engine.execute("to_move").unwrap() != Some("none".to_string()) {
+        let legal = engine.execute("legal_moves").unwrap().unwrap();
+        let mv = engine.execute("genmove").unwrap().unwrap();
+        assert!(legal.lines().any(|l| l == mv), "{} not in {:?}", mv, legal);
+        plies += 1;
+        assert!(plies < 100);
+    }
+    assert!(plies >= 4);
+    let scores = engine.execute("scores").unwrap().unwrap();
+    assert_eq!(scores.split(' ').count(), 2);
+    assert!(engine.execute("genmove").is_err());
+    assert_eq!(engine.execute("legal_moves"), Ok(Some(String::new())));
+
+    // A time limit applies to the agent set, and only to agents that have one.
+    assert_eq!(engine.execute("clear_board"), Ok(Some(String::new())));
+    assert!(engine.execute("genmove 10").is_err());
+    assert_eq!(engine.execute("agent maxn:5000"), Ok(Some(String::new())));
+    assert!(engine.execute("genmove 10").unwrap().is_some());
+    assert_eq!(engine.agent.name(), "maxn:5000");
+}
@@ -0,0 +1,256 @@
+//! Tree search guided by an evaluator that gives move priors and value
+//! estimates, as in AlphaZero. Instead of playing out random games, a new
+//! leaf is scored by the evaluator, and children are selected by the PUCT rule
+//!
+//! ```text
+//! Q(child) + c_puct * P(child) * sqrt(N(node)) / (1 + N(child))
+//! ```
+//!
+//! where `Q` is the average outcome for the color making the move,
+//! `P` is the prior from the evaluator and `N` counts visits.
+
+use rand::Rng;
+use rand::distributions::{Dirichlet, Distribution};
+
+use board::{Board, Color, Move};
+use encoding::{Perspective, action_count};
+use game::{Game, outcomes};
+use treesearch::GameStateMut;
+
+/// Scores positions for `PuctSearch`.
+pub trait Evaluator {
+    /// Policy logits over the `encoding::action_count(board)` actions of the color
+    /// of `view`, and the expected outcome between -1 and 1 of each color,
+    /// listed starting with the color of `view`.
+    fn evaluate(&mut self, board: &Board, view: &Perspective) -> (Vec<f32>, Vec<f32>);
+    /// Whether the evaluator can evaluate positions of `board`;
+    /// `evaluate` may panic on boards that fail the check.
+    fn check(&self, _board: &Board) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl<E: Evaluator + ?Sized> Evaluator for Box<E> {
+    fn evaluate(&mut self, board: &Board, view: &Perspective) -> (Vec<f32>, Vec<f32>) {
+        (**self).evaluate(board, view)
+    }
+
+    fn check(&self, board: &Board) -> Result<(), String> {
+        (**self).check(board)
+    }
+}
+
+/// Equal priors for all moves and an even outcome for every position,
+/// so that only finished games tell the moves apart.
+pub struct UniformEvaluator;
+
+impl Evaluator for UniformEvaluator {
+    fn evaluate(&mut self, board: &Board, _view: &Perspective) -> (Vec<f32>, Vec<f32>) {
+        (vec![0.0; action_count(board)], vec![0.0; board.colors()])
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PuctConfig {
+    /// Weight of the prior relative to the average outcome.
+    pub c_puct: f64,
+    /// Show the evaluator each position from the side of the color to move;
+    /// see `encoding::Perspective`.
+    pub rotate: bool,
+}
+
+impl Default for PuctConfig {
+    fn default() -> Self {
+        PuctConfig { c_puct: 1.5, rotate: true }
+    }
+}
+
+struct Node {
+    /// The move leading here from the parent; `None` is a pass.
+    mv: Option<Move>,
+    /// The color `value_sum` is counted for: the color that made `mv`,
+    /// or the color to move at the root.
+    color: Color,
+    prior: f64,
+    visits: u32,
+    value_sum: f64,
+    /// Indices of the children, once the node has been evaluated.
+    children: Option<Vec<usize>>,
+}
+
+impl Node {
+    fn new(mv: Option<Move>, color: Color, prior: f64) -> Self {
+        Node { mv, color, prior, visits: 0, value_sum: 0.0, children: None }
+    }
+
+    fn mean_value(&self) -> f64 {
+        if self.visits == 0 { 0.0 } else { self.value_sum / self.visits as f64 }
+    }
+}
+
+/// A search tree rooted at the position of the game it was created for.
+pub struct PuctSearch {
+    nodes: Vec<Node>,
+    config: PuctConfig,
+}
+
+impl PuctSearch {
+    pub fn new(game: &Game, config: PuctConfig) -> Self {
+        PuctSearch { nodes: vec![Node::new(None, game.to_move(), 1.0)], config }
+    }
+
+    fn select_child(&self, i: usize) -> usize {
+        let node = &self.nodes[i];
+        let sqrt_visits = (node.visits as f64).sqrt();
+        let score = |&c: &usize| {
+            let child = &self.nodes[c];
+            child.mean_value() + self.config.c_puct * child.prior * sqrt_visits / (1 + child.visits) as f64
+        };
+        let children = node.children.as_ref().unwrap();
+        let mut best = children[0];
+        for c in children.iter() {
+            if score(c) > score(&best) {
+                best = *c;
+            }
+        }
+        best
+    }
+
+    /// Add the children of leaf `i`, which is the current position of `game`,
+    /// and return the evaluator's estimate of the outcome for each color.
+    fn expand<E: Evaluator + ?Sized>(&mut self, i: usize, game: &mut Game, evaluator: &mut E) -> Vec<f64> {
+        let color = game.to_move();
+        let colors = game.board().colors();
+        let view = Perspective::new(game.board(), color, self.config.rotate);
+        let (logits, values) = evaluator.evaluate(game.board(), &view);
+        let moves = if game.must_pass() { vec![None] } else { game.legal_moves().iter().map(|&m| Some(m)).collect() };
+        let logits = moves.iter().map(|&mv| logits[view.encode(game.board(), mv)] as f64).collect::<Vec<_>>();
+        let max = logits.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let weights = logits.iter().map(|l| (l - max).exp()).collect::<Vec<_>>();
+        let sum = weights.iter().sum::<f64>();
+        let mut children = Vec::new();
+        for (&mv, w) in moves.iter().zip(weights) {
+            children.push(self.nodes.len());
+            self.nodes.push(Node::new(mv, color, w / sum));
+        }
+        self.nodes[i].children = Some(children);
+        let mut res = vec![0.0; colors];
+        for (k, &v) in values.iter().enumerate() {
+            res[(color as usize + k) % colors] = v as f64;
+        }
+        res
+    }
+
+    /// Run one simulation from the root; `game` is back in the root position afterwards.
+    pub fn iterate<E: Evaluator + ?Sized>(&mut self, game: &mut Game, evaluator: &mut E) {
+        let mut path = vec![0];
+        let mut i = 0;
+        while self.nodes[i].children.is_some() && game.move_count() > 0 {
+            i = self.select_child(i);
+            game.play(self.nodes[i].mv).unwrap();
+            path.push(i);
+        }
+        let values = if game.move_count() == 0 {
+            outcomes(&game.scores())
+        } else {
+            self.expand(i, game, evaluator)
+        };
+        for &i in path.iter() {
+            let node = &mut self.nodes[i];
+            node.visits += 1;
+            node.value_sum += values[node.color as usize];
+        }
+        for _ in 1..path.len() {
+            game.take_back();
+        }
+    }
+
+    pub fn run<E: Evaluator + ?Sized>(&mut self, game: &mut Game, evaluator: &mut E, iterations: usize) {
+        for _ in 0..iterations {
+            self.iterate(game, evaluator);
+        }
+    }
+
+    /// Mix Dirichlet noise into the priors of the moves at the root, as
+    /// `(1 - fraction) * prior + fraction * noise`, so that self-play also
+    /// tries moves the evaluator dislikes. The root must have been expanded
+    /// by a first iteration; a single move is left alone.
+    pub fn add_root_noise<R: Rng>(&mut self, alpha: f64, fraction: f64, rng: &mut R) {
+        let children = match self.nodes[0].children {
+            Some(ref children) if children.len() > 1 => children.clone(),
+            _ => return,
+        };
+        let noise = Dirichlet::new_with_param(alpha, children.len()).sample(rng);
+        for (&c, n) in children.iter().zip(noise) {
+            let child = &mut self.nodes[c];
+            child.prior = (1.0 - fraction) * child.prior + fraction * n;
+        }
+    }
+
+    /// The moves at the root with their visit counts.
+    pub fn root_visits(&self) -> Vec<(Option<Move>, u32)> {
+        match self.nodes[0].children {
+            Some(ref children) => children.iter().map(|&c| (self.nodes[c].mv, self.nodes[c].visits)).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Average outcome for the color to move at the root.
+    pub fn root_value(&self) -> f64 {
+        self.nodes[0].mean_value()
+    }
+
+    /// The most visited move at the root, or `None` before the first iteration.
+    pub fn best_move(&self) -> Option<Option<Move>> {
+        self.root_visits().into_iter().max_by_key(|&(_, visits)| visits).map(|(mv, _)| mv)
+    }
+}
+
+#[test]
+fn test_puct_finds_winning_move() {
+    // See `minimax::corridor`: playing the domino first wins on a 1x3 board.
+    let mut g = Game::new(Board::new(::pieces::compute_pieces(2), 1, 3, &[(0, 0), (2, 0)]).unwrap());
+    let mut search = PuctSearch::new(&g, PuctConfig::default());
+    search.run(&mut g, &mut UniformEvaluator, 200);
+    assert!(g.history().is_empty());
+    let (piece, _, _) = search.best_move().unwrap().unwrap();
+    assert_eq!(piece, 1);
+    assert!(search.root_value() > 0.5);
+    assert_eq!(search.root_visits().iter().map(|&(_, v)| v).sum::<u32>(), 199);
+}
+
+#[test]
+fn test_root_noise() {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    let mut g = Game::standard(6, 2, 3).unwrap();
+    let mut search = PuctSearch::new(&g, PuctConfig::default());
+    search.run(&mut g, &mut UniformEvaluator, 1);
+    let priors = |s: &PuctSearch| s.nodes[0].children.as_ref().unwrap().iter().map(|&c| s.nodes[c].prior).collect::<Vec<_>>();
+    let before = priors(&search);
+    search.add_root_noise(0.3, 0.25, &mut StdRng::seed_from_u64(1));
+    let after = priors(&search);
+    assert_ne!(before, after);
+    assert!((after.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    assert!(after.iter().all(|&p| p >= 0.75 * before[0] - 1e-12));
+}
+
+#[test]
+fn test_priors_guide_search() {
+    // An evaluator that only likes the monomino leads the search to it first.
+    struct Monomino;
+    impl Evaluator for Monomino {
+        fn evaluate(&mut self, board: &Board, _view: &Perspective) -> (Vec<f32>, Vec<f32>) {
+            let mut logits = vec![-10.0; action_count(board)];
+            for l in logits.iter_mut().take(::encoding::ORIENTATIONS * board.size()) {
+                *l = 10.0;
+            }
+            (logits, vec![0.0; board.colors()])
+        }
+    }
+    let mut g = Game::standard(6, 2, 3).unwrap();
+    let mut search = PuctSearch::new(&g, PuctConfig::default());
+    search.run(&mut g, &mut Monomino, 10);
+    let (piece, _, _) = search.best_move().unwrap().unwrap();
+    assert_eq!(piece, 0);
+}
@@ -0,0 +1,128 @@
+//! Elo ratings from pairwise results, fitted as a Bradley-Terry model.
+
+/// Rating difference in Elo that corresponds to a factor of e in the odds of winning.
+const ELO_PER_NAT: f64 = 400.0 / ::std::f64::consts::LN_10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rating {
+    /// Elo rating relative to the average player.
+    pub elo: f64,
+    /// Half width of the 95% confidence interval of `elo`.
+    pub margin: f64,
+}
+
+/// Accumulates pairwise results between `n` players.
+#[derive(Debug, Clone)]
+pub struct Results {
+    /// `games[i][j]` is the number of games between `i` and `j`.
+    games: Vec<Vec<f64>>,
+    /// `points[i][j]` is the number of points `i` scored against `j`,
+    /// counting a draw as half a point.
+    points: Vec<Vec<f64>>,
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+impl Results {
+    pub fn new(n: usize) -> Self {
+        Results {
+            games: vec![vec![0.0; n]; n],
+            points: vec![vec![0.0; n]; n],
+        }
+    }
+
+    /// Record a game between `i` and `j` where `i` scored `score` (1 for a win, 0.5 for a draw).
+    pub fn add(&mut self, i: usize, j: usize, score: f64) {
+        if i == j {
+            return;
+        }
+        self.games[i][j] += 1.0;
+        self.games[j][i] += 1.0;
+        self.points[i][j] += score;
+        self.points[j][i] += 1.0 - score;
+    }
+
+    /// Record the pairwise outcomes of one multi-player game,
+    /// where `players[s]` got `scores[s]` in seat `s`.
+    pub fn add_game(&mut self, players: &[usize], scores: &[i32]) {
+        for a in 0..players.len() {
+            for b in (a + 1)..players.len() {
+                let score = if scores[a] > scores[b] { 1.0 } else if scores[a] < scores[b] { 0.0 } else { 0.5 };
+                self.add(players[a], players[b], score);
+            }
+        }
+    }
+
+    /// Maximum likelihood ratings, with a prior of one draw against a player of
+    /// average strength so that ratings stay finite for unbeaten players.
+    pub fn ratings(&self) -> Vec<Rating> {
+        let n = self.games.len();
+        let mut r = vec![0.0f64; n];
+        for _ in 0..1000 {
+            let mut max_step = 0.0f64;
+            for i in 0..n {
+                // Newton step on the log likelihood of player i.
+                let mut gradient = 0.5 - sigmoid(r[i]);
+                let mut curvature = sigmoid(r[i]) * (1.0 - sigmoid(r[i]));
+                for j in 0..n {
+                    if self.games[i][j] == 0.0 {
+                        continue;
+                    }
+                    let p = sigmoid(r[i] - r[j]);
+                    gradient += self.points[i][j] - self.games[i][j] * p;
+                    curvature += self.games[i][j] * p * (1.0 - p);
+                }
+                let step = gradient / curvature;
+                r[i] += step;
+                max_step = max_step.max(step.abs());
+            }
+            if max_step < 1e-9 {
+                break;
+            }
+        }
+        let mean = r.iter().sum::<f64>() / n.max(1) as f64;
+        (0..n).map(|i| {
+            let mut information = sigmoid(r[i]) * (1.0 - sigmoid(r[i]));
+            for j in 0..n {
+                let p = sigmoid(r[i] - r[j]);
+                information += self.games[i][j] * p * (1.0 - p);
+            }
+            Rating {
+                elo: (r[i] - mean) * ELO_PER_NAT,
+                margin: 1.96 * ELO_PER_NAT / information.sqrt(),
+            }
+        }).collect()
+    }
+}
+
+#[test]
+fn test_equal_players() {
+    let mut results = Results::new(2);
+    for _ in 0..10 {
+        results.add(0, 1, 1.0);
+        results.add(0, 1, 0.0);
+    }
+    let ratings = results.ratings();
+    assert!(ratings[0].elo.abs() < 1e-6);
+    assert!(ratings[1].elo.abs() < 1e-6);
+    assert!(ratings[0].margin > 0.0);
+}
+
+#[test]
+fn test_stronger_player() {
+    let mut results = Results::new(3);
+    for _ in 0..30 {
+        results.add_game(&[0, 1, 2], &[10, 5, 0]);
+    }
+    results.add_game(&[0, 1, 2], &[0, 5, 10]);
+    let ratings = results.ratings();
+    assert!(ratings[0].elo > ratings[1].elo);
+    assert!(ratings[1].elo > ratings[2].elo);
+    assert!((ratings.iter().map(|r| r.elo).sum::<f64>()).abs() < 1e-6);
+    // More games give tighter intervals.
+    let mut few = Results::new(3);
+    few.add_game(&[0, 1, 2], &[10, 5, 0]);
+    assert!(few.ratings()[0].margin > ratings[0].margin);
+}
@@ -0,0 +1,164 @@
+//! Annealing schedules for the temperature of the tiling search.
+//!
+//! The temperature of `treesearch` is the sharpness of the leaf selection:
+//! leaves are chosen with probability proportional to `exp(value * temperature)`,
+//! so the search explores broadly at low temperatures and concentrates on the
+//! best leaves as the temperature rises.
+
+use std::fmt;
+
+/// The temperature as a function of the number of iterations done so far.
+pub trait Schedule: fmt::Display {
+    fn temperature(&self, iteration: usize) -> f64;
+}
+
+/// The same temperature throughout.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constant(pub f64);
+
+impl Schedule for Constant {
+    fn temperature(&self, _iteration: usize) -> f64 {
+        self.0
+    }
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "constant:{}", self.0)
+    }
+}
+
+/// `start + rate * iteration`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Linear {
+    pub start: f64,
+    pub rate: f64,
+}
+
+impl Schedule for Linear {
+    fn temperature(&self, iteration: usize) -> f64 {
+        self.start + self.rate * iteration as f64
+    }
+}
+
+impl fmt::Display for Linear {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "linear:{}:{}", self.rate, self.start)
+    }
+}
+
+/// `start * factor^iteration`, which reaches infinity after a while if `factor`
+/// is above 1; the search then always picks the best leaves.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exponential {
+    pub start: f64,
+    pub factor: f64,
+}
+
+impl Schedule for Exponential {
+    fn temperature(&self, iteration: usize) -> f64 {
+        self.start * self.factor.powf(iteration as f64)
+    }
+}
+
+impl fmt::Display for Exponential {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "exponential:{}:{}", self.start, self.factor)
+    }
+}
+
+/// `scale * ln(iteration + 2)`, the slow schedule of classic simulated annealing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Logarithmic {
+    pub scale: f64,
+}
+
+impl Schedule for Logarithmic {
+    fn temperature(&self, iteration: usize) -> f64 {
+        self.scale * (iteration as f64 + 2.0).ln()
+    }
+}
+
+impl fmt::Display for Logarithmic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "logarithmic:{}", self.scale)
+    }
+}
+
+/// Another schedule started over every `period` iterations,
+/// so that the search periodically goes back to exploring.
+pub struct Restart {
+    pub period: usize,
+    pub schedule: Box<dyn Schedule>,
+}
+
+impl Schedule for Restart {
+    fn temperature(&self, iteration: usize) -> f64 {
+        self.schedule.temperature(iteration % self.period)
+    }
+}
+
+impl fmt::Display for Restart {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "restart:{}:{}", self.period, self.schedule)
+    }
+}
+
+/// The schedule of `run_treesearch`.
+pub const DEFAULT_SCHEDULE: &str = "linear:0.00001";
+
+/// Parse a schedule: `constant:T`, `linear:RATE[:START]` where `START`
+/// defaults to `RATE`, `exponential:START:FACTOR`, `logarithmic:SCALE` or
+/// `restart:PERIOD:SCHEDULE`. Each parses back from its `Display`.
+pub fn parse_schedule(spec: &str) -> Result<Box<dyn Schedule>, String> {
+    let mut parts = spec.splitn(2, ':');
+    let name = parts.next().unwrap();
+    let rest = parts.next().unwrap_or("");
+    if name == "restart" {
+        let mut parts = rest.splitn(2, ':');
+        let period = parts.next().unwrap().parse::<usize>().ok().filter(|&p| p > 0);
+        return match (period, parts.next()) {
+            (Some(period), Some(inner)) => Ok(Box::new(Restart { period, schedule: parse_schedule(inner)? })),
+            _ => Err(format!("invalid schedule {:?}", spec)),
+        };
+    }
+    let args = if rest.is_empty() { Vec::new() } else { rest.split(':').map(|a| a.parse::<f64>()).collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("invalid number in schedule {:?}", spec))? };
+    if args.iter().any(|a| !a.is_finite()) {
+        return Err(format!("infinite number in schedule {:?}", spec));
+    }
+    match (name, &args[..]) {
+        ("constant", &[t]) => Ok(Box::new(Constant(t))),
+        ("linear", &[rate]) => Ok(Box::new(Linear { start: rate, rate })),
+        ("linear", &[rate, start]) => Ok(Box::new(Linear { start, rate })),
+        ("exponential", &[start, factor]) if factor > 0.0 => Ok(Box::new(Exponential { start, factor })),
+        ("logarithmic", &[scale]) => Ok(Box::new(Logarithmic { scale })),
+        _ => Err(format!("invalid schedule {:?}", spec)),
+    }
+}
+
+#[test]
+fn test_schedules() {
+    let linear = parse_schedule("linear:0.5").unwrap();
+    assert_eq!((linear.temperature(0), linear.temperature(3)), (0.5, 2.0));
+    assert_eq!(parse_schedule("linear:0.5:1").unwrap().temperature(2), 2.0);
+    assert_eq!(parse_schedule("constant:3").unwrap().temperature(1000), 3.0);
+    assert_eq!(parse_schedule("exponential:2:1.5").unwrap().temperature(2), 4.5);
+    let log = parse_schedule("logarithmic:2").unwrap();
+    assert!((log.temperature(0) - 2.0 * 2f64.ln()).abs() < 1e-12);
+    let restart = parse_schedule("restart:10:linear:1:0").unwrap();
+    assert_eq!((restart.temperature(9), restart.temperature(10), restart.temperature(23)), (9.0, 0.0, 3.0));
+}
+
+#[test]
+fn test_parse_schedule() {
+    for spec in &["constant:0.1", "linear:0.00001:0.00001", "exponential:0.001:1.0001",
+                  "logarithmic:0.5", "restart:1000:exponential:0.1:1.01"] {
+        assert_eq!(&parse_schedule(spec).unwrap().to_string(), spec);
+    }
+    assert_eq!(parse_schedule(DEFAULT_SCHEDULE).unwrap().to_string(), "linear:0.00001:0.00001");
+    for spec in &["linear", "linear:x", "constant:1:2", "exponential:1:0", "restart:0:constant:1",
+                  "restart:10", "cosine:1", "constant:inf", "linear:NaN"] {
+        assert!(parse_schedule(spec).is_err(), "{}", spec);
+    }
+}
@@ -0,0 +1,396 @@
+//! Self-play games of `PuctSearch` against itself, stored as training data.
+//!
+//! A game is recorded as the positions where the color to move had a choice:
+//! the cells of the board, the color to move, the visits of the search for each
+//! action and the action played, followed by the final scores. Forced passes
+//! are not recorded, since they carry nothing to learn; `GameRecord::examples`
+//! replays a game to turn its positions into network inputs and targets.
+//!
+//! Actions are encoded as seen by color 0 without turning the board,
+//! i.e. with `Perspective::new(board, color, false)`.
+//!
+//! Files are little-endian: the magic `BKSP`, then the `u32`s version (1),
+//! board size, number of colors and largest piece size of `Game::standard`.
+//! Each game follows as the `u32` number of positions, an `i32` score per
+//! color, and the resigning color as a `u8` (255 if nobody resigned). Each
+//! position is the color to move as a `u8`, a `u8` per cell with its color or
+//! 255 if empty, the `u32` action played, and the `u32` number of actions
+//! searched followed by `u32` action and `u32` visit pairs.
+
+use std::io::{self, Read, Write};
+
+use rand::Rng;
+
+use board::Color;
+use encoding::Perspective;
+use error::BlokusError;
+use game::{Game, outcomes};
+use puct::{Evaluator, PuctConfig, PuctSearch};
+use treesearch::GameStateMut;
+
+const MAGIC: &[u8; 4] = b"BKSP";
+const VERSION: u32 = 1;
+const NONE: u8 = 255;
+
+/// The game every record in a file starts from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Header {
+    pub size: usize,
+    pub colors: usize,
+    pub piece_size: usize,
+}
+
+impl Header {
+    pub fn new_game(&self) -> Result<Game, BlokusError> {
+        Game::standard(self.size, self.colors, self.piece_size)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    pub to_move: Color,
+    pub cells: Vec<Option<Color>>,
+    /// Visits of the search for each action it tried.
+    pub visits: Vec<(u32, u32)>,
+    pub played: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameRecord {
+    pub positions: Vec<Position>,
+    pub scores: Vec<i32>,
+    pub resigned: Option<Color>,
+}
+
+/// A position as network input, with the targets for both heads.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Example {
+    /// `Perspective::observation` for the color to move.
+    pub observation: Vec<f32>,
+    /// Actions in the same perspective with their share of the visits.
+    pub policy: Vec<(u32, f32)>,
+    /// The result of each color, listed starting with the color to move.
+    pub outcome: Vec<f32>,
+}
+
+impl GameRecord {
+    /// The result of the game for each color as in `game::outcomes`,
+    /// except that a color that resigned has lost.
+    pub fn outcomes(&self) -> Vec<f64> {
+        let mut scores = self.scores.clone();
+        if let Some(c) = self.resigned {
+            scores[c as usize] = self.scores.iter().min().unwrap() - 1;
+        }
+        outcomes(&scores)
+    }
+
+    /// Replay the game from `header` and give an example for each position;
+    /// fails if the record does not match the game.
+    pub fn examples(&self, header: &Header, rotate: bool) -> Result<Vec<Example>, String> {
+        let mut game = header.new_game().map_err(|e| e.to_string())?;
+        let result = self.outcomes();
+        let colors = header.colors;
+        let mut examples = Vec::new();
+        for (i, position) in self.positions.iter().enumerate() {
+            while game.move_count() > 0 && game.must_pass() {
+                game.play(None).unwrap();
+            }
+            let cells = (0..game.board().size()).map(|j| game.board().at(j)).collect::<Vec<_>>();
+            if game.move_count() == 0 || game.to_move() != position.to_move || cells != position.cells {
+                return Err(format!("position {} does not match the game", i + 1));
+            }
+            let color = position.to_move;
+            let absolute = Perspective::new(game.board(), color, false);
+            let view = Perspective::new(game.board(), color, rotate);
+            let total = position.visits.iter().map(|&(_, v)| v).sum::<u32>().max(1);
+            let mut policy = Vec::new();
+            for &(action, visits) in position.visits.iter() {
+                let mv = absolute.decode(game.board(), action as usize)?;
+                policy.push((view.encode(game.board(), mv) as u32, visits as f32 / total as f32));
+            }
+            examples.push(Example {
+                observation: view.observation(game.board()),
+                policy,
+                outcome: (0..colors).map(|k| result[(color as usize + k) % colors] as f32).collect(),
+            });
+            let mv = absolute.decode(game.board(), position.played as usize)?;
+            game.play(mv).map_err(|e| format!("illegal move at position {}: {}", i + 1, e))?;
+        }
+        Ok(examples)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SelfPlayConfig {
+    pub iterations: usize,
+    pub puct: PuctConfig,
+    /// Moves are played with probability proportional to `visits^(1 / temperature)`;
+    /// 0 always plays the most visited move.
+    pub temperature: f64,
+    /// The temperature drops to 0 after this many moves, passes included.
+    pub temperature_moves: usize,
+    /// Concentration of the Dirichlet noise added to the priors at the root.
+    pub noise_alpha: f64,
+    /// Weight of the noise; 0 disables it.
+    pub noise_fraction: f64,
+    /// A color resigns when its expected outcome at the root falls below this.
+    pub resign_threshold: Option<f64>,
+}
+
+impl Default for SelfPlayConfig {
+    fn default() -> Self {
+        SelfPlayConfig {
+            iterations: 200,
+            puct: PuctConfig::default(),
+            temperature: 1.0,
+            temperature_moves: 16,
+            noise_alpha: 0.3,
+            noise_fraction: 0.25,
+            resign_threshold: None,
+        }
+    }
+}
+
+fn sample_visits<R: Rng>(visits: &[(u32, u32)], temperature: f64, rng: &mut R) -> usize {
+    let best = (0..visits.len()).max_by_key(|&i| visits[i].1).unwrap();
+    let max = visits[best].1 as f64;
+    if temperature <= 0.0 || max == 0.0 {
+        return best;
+    }
+    let weights = visits.iter().map(|&(_, v)| (v as f64 / max).powf(1.0 / temperature)).collect::<Vec<_>>();
+    let mut x = rng.gen::<f64>() * weights.iter().sum::<f64>();
+    for (i, w) in weights.iter().enumerate() {
+        if x < *w {
+            return i;
+        }
+        x -= w;
+    }
+    best
+}
+
+/// Play `game` to the end, or until a color resigns.
+pub fn play_game<E: Evaluator + ?Sized, R: Rng>(game: &mut Game, evaluator: &mut E, config: &SelfPlayConfig,
+                                                 rng: &mut R) -> GameRecord {
+    let mut positions = Vec::new();
+    let mut resigned = None;
+    while game.move_count() > 0 {
+        if game.must_pass() {
+            game.play(None).unwrap();
+            continue;
+        }
+        let color = game.to_move();
+        let mut search = PuctSearch::new(game, config.puct.clone());
+        search.run(game, evaluator, 1);
+        if config.noise_fraction > 0.0 {
+            search.add_root_noise(config.noise_alpha, config.noise_fraction, rng);
+        }
+        search.run(game, evaluator, config.iterations.saturating_sub(1));
+        if config.resign_threshold.map_or(false, |t| search.root_value() < t) {
+            resigned = Some(color);
+            break;
+        }
+        let absolute = Perspective::new(game.board(), color, false);
+        let moves = search.root_visits();
+        let visits = moves.iter().map(|&(mv, v)| (absolute.encode(game.board(), mv) as u32, v)).collect::<Vec<_>>();
+        let temperature = if game.history().len() < config.temperature_moves { config.temperature } else { 0.0 };
+        let i = sample_visits(&visits, temperature, rng);
+        positions.push(Position {
+            to_move: color,
+            cells: (0..game.board().size()).map(|j| game.board().at(j)).collect(),
+            visits: visits.into_iter().filter(|&(_, v)| v > 0).collect(),
+            played: absolute.encode(game.board(), moves[i].0) as u32,
+        });
+        game.play(moves[i].0).unwrap();
+    }
+    GameRecord { positions, scores: game.scores(), resigned }
+}
+
+fn write_u32<W: Write>(w: &mut W, x: u32) -> io::Result<()> {
+    w.write_all(&x.to_le_bytes())
+}
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u8<R: Read>(r: &mut R) -> io::Result<u8> {
+    let mut buf = [0; 1];
+    r.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub fn write_header<W: Write>(w: &mut W, header: &Header) -> io::Result<()> {
+    w.write_all(MAGIC)?;
+    for &x in [VERSION as usize, header.size, header.colors, header.piece_size].iter() {
+        write_u32(w, x as u32)?;
+    }
+    Ok(())
+}
+
+pub fn write_game<W: Write>(w: &mut W, record: &GameRecord) -> io::Result<()> {
+    write_u32(w, record.positions.len() as u32)?;
+    for &s in record.scores.iter() {
+        w.write_all(&s.to_le_bytes())?;
+    }
+    w.write_all(&[record.resigned.unwrap_or(NONE)])?;
+    for position in record.positions.iter() {
+        w.write_all(&[position.to_move])?;
+        let cells = position.cells.iter().map(|c| c.unwrap_or(NONE)).collect::<Vec<_>>();
+        w.write_all(&cells)?;
+        write_u32(w, position.played)?;
+        write_u32(w, position.visits.len() as u32)?;
+        for &(action, visits) in position.visits.iter() {
+            write_u32(w, action)?;
+            write_u32(w, visits)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads the games of a self-play file one at a time.
+pub struct Reader<R> {
+    inner: R,
+    header: Header,
+}
+
+impl<R: Read> Reader<R> {
+    /// Read the header of the file.
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        inner.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a self-play file"));
+        }
+        if read_u32(&mut inner)? != VERSION {
+            return Err(invalid("unsupported self-play file version"));
+        }
+        let size = read_u32(&mut inner)? as usize;
+        let colors = read_u32(&mut inner)? as usize;
+        let piece_size = read_u32(&mut inner)? as usize;
+        let header = Header { size, colors, piece_size };
+        if header.new_game().is_err() {
+            return Err(invalid("unsupported game in self-play file"));
+        }
+        Ok(Reader { inner, header })
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// The next game, or `None` at the end of the file.
+    pub fn next_game(&mut self) -> io::Result<Option<GameRecord>> {
+        let mut buf = [0; 4];
+        let mut read = 0;
+        while read < 4 {
+            match self.inner.read(&mut buf[read..])? {
+                0 if read == 0 => return Ok(None),
+                0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                n => read += n,
+            }
+        }
+        let count = u32::from_le_bytes(buf);
+        let r = &mut self.inner;
+        let colors = self.header.colors;
+        let mut scores = Vec::new();
+        for _ in 0..colors {
+            scores.push(read_u32(r)? as i32);
+        }
+        let resigned = match read_u8(r)? {
+            NONE => None,
+            c if (c as usize) < colors => Some(c),
+            _ => return Err(invalid("invalid resigning color")),
+        };
+        let mut positions = Vec::new();
+        for _ in 0..count {
+            let to_move = read_u8(r)?;
+            let mut cells = vec![0; self.header.size * self.header.size];
+            r.read_exact(&mut cells)?;
+            if (to_move as usize) >= colors || cells.iter().any(|&c| c != NONE && c as usize >= colors) {
+                return Err(invalid("invalid color in position"));
+            }
+            let played = read_u32(r)?;
+            let mut visits = Vec::new();
+            for _ in 0..read_u32(r)? {
+                visits.push((read_u32(r)?, read_u32(r)?));
+            }
+            positions.push(Position {
+                to_move,
+                cells: cells.into_iter().map(|c| if c == NONE { None } else { Some(c) }).collect(),
+                visits,
+                played,
+            });
+        }
+        Ok(Some(GameRecord { positions, scores, resigned }))
+    }
+}
+
+#[test]
+fn test_selfplay_records() {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use encoding::{action_count, observation_shape};
+    use puct::UniformEvaluator;
+    let header = Header { size: 5, colors: 2, piece_size: 2 };
+    let config = SelfPlayConfig { iterations: 20, ..SelfPlayConfig::default() };
+    let mut rng = StdRng::seed_from_u64(3);
+    let mut g = header.new_game().unwrap();
+    let record = play_game(&mut g, &mut UniformEvaluator, &config, &mut rng);
+    assert_eq!(g.move_count(), 0);
+    assert_eq!(record.scores, g.scores());
+    assert!(record.resigned.is_none());
+    assert!(!record.positions.is_empty());
+
+    let examples = record.examples(&header, true).unwrap();
+    assert_eq!(examples.len(), record.positions.len());
+    let (planes, rows, cols) = observation_shape(g.board());
+    let result = outcomes(&g.scores());
+    for (example, position) in examples.iter().zip(record.positions.iter()) {
+        assert_eq!(example.observation.len(), planes * rows * cols);
+        assert!((example.policy.iter().map(|&(_, p)| p).sum::<f32>() - 1.0).abs() < 1e-5);
+        assert!(example.policy.iter().all(|&(a, _)| (a as usize) < action_count(g.board())));
+        assert_eq!(example.outcome[0] as f64, result[position.to_move as usize]);
+    }
+
+    // Resigning at once leaves nothing to record, and the resigning color loses.
+    let eager = SelfPlayConfig { resign_threshold: Some(1.0), noise_fraction: 0.0, ..config.clone() };
+    let resigned = play_game(&mut header.new_game().unwrap(), &mut UniformEvaluator, &eager, &mut rng);
+    assert_eq!(resigned.resigned, Some(0));
+    assert_eq!(resigned.outcomes(), vec![-1.0, 1.0]);
+
+    let mut bytes = Vec::new();
+    write_header(&mut bytes, &header).unwrap();
+    write_game(&mut bytes, &record).unwrap();
+    write_game(&mut bytes, &resigned).unwrap();
+    let mut reader = Reader::new(&bytes[..]).unwrap();
+    assert_eq!(reader.header(), &header);
+    assert_eq!(reader.next_game().unwrap(), Some(record.clone()));
+    assert_eq!(reader.next_game().unwrap(), Some(resigned));
+    assert_eq!(reader.next_game().unwrap(), None);
+    let mut truncated = Reader::new(&bytes[..bytes.len() - 2]).unwrap();
+    truncated.next_game().unwrap();
+    assert!(truncated.next_game().is_err());
+
+    let mut tampered = record.clone();
+    tampered.positions[1].played = tampered.positions[0].played;
+    assert!(tampered.examples(&header, false).is_err());
+}
+
+#[test]
+fn test_sample_visits() {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    let mut rng = StdRng::seed_from_u64(1);
+    let visits = [(0, 1), (1, 8), (2, 0)];
+    assert_eq!(sample_visits(&visits, 0.0, &mut rng), 1);
+    let picks = (0..1000).map(|_| sample_visits(&visits, 1.0, &mut rng)).collect::<Vec<_>>();
+    assert!(!picks.contains(&2));
+    let ones = picks.iter().filter(|&&i| i == 1).count();
+    assert!(ones > 800 && ones < 960);
+}
@@ -0,0 +1,82 @@
+/// One of the eight symmetries of the square, acting on board coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    Identity,
+    Rot90,
+    Rot180,
+    Rot270,
+    MirrorX,
+    MirrorY,
+    Transpose,
+    AntiTranspose,
+}
+
+pub const TRANSFORMS: [Transform; 8] = [
+    Transform::Identity,
+    Transform::Rot90,
+    Transform::Rot180,
+    Transform::Rot270,
+    Transform::MirrorX,
+    Transform::MirrorY,
+    Transform::Transpose,
+    Transform::AntiTranspose,
+];
+
+impl Transform {
+    /// Whether the transform maps a `rows` by `cols` rectangle onto itself.
+    pub fn fits(self, rows: usize, cols: usize) -> bool {
+        !self.swaps_axes() || rows == cols
+    }
+
+    /// Whether the transform maps rows to columns.
+    pub fn swaps_axes(self) -> bool {
+        matches!(self, Transform::Rot90 | Transform::Rot270 | Transform::Transpose | Transform::AntiTranspose)
+    }
+
+    /// Image of the cell `(x, y)` on a board of the given dimensions.
+    /// Only meaningful if `self.fits(rows, cols)`.
+    pub fn apply(self, rows: usize, cols: usize, (x, y): (usize, usize)) -> (usize, usize) {
+        let (w, h) = (cols - 1, rows - 1);
+        match self {
+            Transform::Identity => (x, y),
+            Transform::Rot90 => (h - y, x),
+            Transform::Rot180 => (w - x, h - y),
+            Transform::Rot270 => (y, w - x),
+            Transform::MirrorX => (w - x, y),
+            Transform::MirrorY => (x, h - y),
+            Transform::Transpose => (y, x),
+            Transform::AntiTranspose => (h - y, w - x),
+        }
+    }
+
+    /// Permutation of cell indices induced by the transform.
+    pub fn cell_map(self, rows: usize, cols: usize) -> Vec<usize> {
+        (0..rows * cols).map(|i| {
+            let (x, y) = self.apply(rows, cols, (i % cols, i / cols));
+            x + y * cols
+        }).collect()
+    }
+}
+
+#[test]
+fn test_cell_maps_are_permutations() {
+    for &t in TRANSFORMS.iter() {
+        for &(rows, cols) in &[(3, 3), (2, 5)] {
+            if !t.fits(rows, cols) {
+                continue;
+            }
+            let mut m = t.cell_map(rows, cols);
+            m.sort();
+            assert_eq!(m, (0..rows * cols).collect::<Vec<_>>());
+        }
+    }
+}
+
+#[test]
+fn test_rot90_order() {
+    let m = Transform::Rot90.cell_map(4, 4);
+    for i in 0..16 {
+        assert_eq!(m[m[m[m[i]]]], i);
+        assert_ne!(m[m[i]], i);
+    }
+}
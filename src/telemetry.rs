@@ -0,0 +1,351 @@
+//! Progress reports of a running tree search.
+//!
+//! `Telemetry` is fed the value of every playout by the search and, at most
+//! once per interval, passes a `Progress` summary to its observers. The
+//! built-in observers write a status line for humans, JSON lines or CSV.
+//! An observer that fails to write is dropped, and its error kept for the caller.
+
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use schedule::Schedule;
+use treesearch::Tree;
+
+/// A snapshot of the search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    /// Iterations of the search, including those before it was resumed.
+    pub iterations: usize,
+    /// Time since the telemetry was created.
+    pub elapsed: Duration,
+    /// Iterations per second since the previous report.
+    pub rate: f64,
+    pub nodes: usize,
+    /// Bytes taken by the tree.
+    pub memory: usize,
+    /// Number of nodes at each depth.
+    pub depths: Vec<usize>,
+    /// Best playout value so far, if any.
+    pub best_value: Option<f64>,
+    /// Temperature of the next iteration.
+    pub temperature: f64,
+    /// Description of the schedule of the temperature, as in `schedule::parse_schedule`.
+    pub schedule: String,
+}
+
+impl Progress {
+    pub fn max_depth(&self) -> usize {
+        self.depths.len().saturating_sub(1)
+    }
+
+    pub fn mean_depth(&self) -> f64 {
+        let nodes = self.depths.iter().sum::<usize>();
+        let total = self.depths.iter().enumerate().map(|(d, &n)| d * n).sum::<usize>();
+        if nodes == 0 { 0.0 } else { total as f64 / nodes as f64 }
+    }
+}
+
+/// Receives progress reports.
+pub trait Observer {
+    fn progress(&mut self, progress: &Progress) -> io::Result<()>;
+    /// The last report, when the search stops.
+    fn finish(&mut self, progress: &Progress) -> io::Result<()> {
+        self.progress(progress)
+    }
+}
+
+/// Collects playout values and reports to its observers at a fixed interval.
+pub struct Telemetry {
+    observers: Vec<Box<dyn Observer>>,
+    interval: Duration,
+    start: Instant,
+    last_report: Instant,
+    iterations: usize,
+    last_iterations: usize,
+    best_value: Option<f64>,
+    /// Errors of the observers that were dropped.
+    errors: Vec<io::Error>,
+}
+
+impl Telemetry {
+    /// Telemetry without observers that reports every `interval`.
+    pub fn new(interval: Duration) -> Self {
+        let now = Instant::now();
+        Telemetry {
+            observers: Vec::new(),
+            interval,
+            start: now,
+            last_report: now,
+            iterations: 0,
+            last_iterations: 0,
+            best_value: None,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn add_observer<O: Observer + 'static>(&mut self, observer: O) {
+        self.observers.push(Box::new(observer));
+    }
+
+    /// Errors of the observers that failed and were dropped, in order.
+    pub fn errors(&self) -> &[io::Error] {
+        &self.errors
+    }
+
+    /// Start from the best value of an earlier run.
+    pub fn set_best_value(&mut self, value: f64) {
+        self.best_value = Some(value);
+    }
+
+    /// Count an iteration whose playout had `value`.
+    pub fn record(&mut self, value: f64) {
+        self.iterations += 1;
+        if self.best_value.map_or(true, |best| value > best) {
+            self.best_value = Some(value);
+        }
+    }
+
+    pub fn is_due(&self) -> bool {
+        !self.observers.is_empty() && self.last_report.elapsed() >= self.interval
+    }
+
+    fn progress(&mut self, tree: &Tree, schedule: &dyn Schedule) -> Progress {
+        let now = Instant::now();
+        let seconds = (now - self.last_report).as_secs_f64();
+        let rate = if seconds > 0.0 { (self.iterations - self.last_iterations) as f64 / seconds } else { 0.0 };
+        self.last_report = now;
+        self.last_iterations = self.iterations;
+        Progress {
+            iterations: tree.visits(),
+            elapsed: now - self.start,
+            rate,
+            nodes: tree.node_count(),
+            memory: tree.memory(),
+            depths: tree.depth_histogram(),
+            best_value: self.best_value,
+            temperature: schedule.temperature(tree.visits()),
+            schedule: schedule.to_string(),
+        }
+    }
+
+    pub fn report(&mut self, tree: &Tree, schedule: &dyn Schedule) {
+        if self.observers.is_empty() {
+            return;
+        }
+        let progress = self.progress(tree, schedule);
+        self.notify(|o| o.progress(&progress));
+    }
+
+    pub fn finish(&mut self, tree: &Tree, schedule: &dyn Schedule) {
+        if self.observers.is_empty() {
+            return;
+        }
+        let progress = self.progress(tree, schedule);
+        self.notify(|o| o.finish(&progress));
+    }
+
+    /// Call `f` on every observer, dropping those that fail.
+    fn notify<F: FnMut(&mut dyn Observer) -> io::Result<()>>(&mut self, mut f: F) {
+        let errors = &mut self.errors;
+        self.observers.retain_mut(|o| match f(&mut **o) {
+            Ok(()) => true,
+            Err(e) => {
+                errors.push(e);
+                false
+            },
+        });
+    }
+}
+
+/// Overwrites a single line on a terminal.
+pub struct StatusLine<W: Write>(pub W);
+
+impl<W: Write> Observer for StatusLine<W> {
+    fn progress(&mut self, p: &Progress) -> io::Result<()> {
+        let best = p.best_value.map_or("-".to_string(), |v| format!("{:.2}", v));
+        write!(self.0, "\r\x1B[K{} iterations, {:.0}/s, {} nodes, {:.1} MB, depth {:.1} (max {}), best {}, T={:.4} ({})",
+               p.iterations, p.rate, p.nodes, p.memory as f64 / (1 << 20) as f64,
+               p.mean_depth(), p.max_depth(), best, p.temperature, p.schedule)?;
+        self.0.flush()
+    }
+
+    fn finish(&mut self, p: &Progress) -> io::Result<()> {
+        self.progress(p)?;
+        writeln!(self.0)
+    }
+}
+
+fn number(x: Option<f64>) -> String {
+    match x {
+        Some(x) if x.is_finite() => x.to_string(),
+        _ => "null".to_string(),
+    }
+}
+
+/// `s` as a JSON string literal.
+fn string(s: &str) -> String {
+    let mut res = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            c if (c as u32) < 0x20 => res.push_str(&format!("\\u{:04x}", c as u32)),
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+/// Writes each report as a JSON object on its own line.
+pub struct JsonLines<W: Write>(pub W);
+
+impl<W: Write> Observer for JsonLines<W> {
+    fn progress(&mut self, p: &Progress) -> io::Result<()> {
+        let depths = p.depths.iter().map(|d| d.to_string()).collect::<Vec<_>>().join(",");
+        writeln!(self.0, "{{\"iterations\":{},\"elapsed\":{},\"rate\":{},\"nodes\":{},\"memory\":{},\"depths\":[{}],\"best_value\":{},\"temperature\":{},\"schedule\":{}}}",
+                 p.iterations, p.elapsed.as_secs_f64(), number(Some(p.rate)), p.nodes, p.memory, depths,
+                 number(p.best_value), number(Some(p.temperature)), string(&p.schedule))?;
+        self.0.flush()
+    }
+}
+
+/// Writes a header and then a row per report; the depth distribution
+/// is a single column of counts separated by semicolons.
+pub struct Csv<W: Write> {
+    writer: W,
+    header_written: bool,
+}
+
+impl<W: Write> Csv<W> {
+    pub fn new(writer: W) -> Self {
+        Csv { writer, header_written: false }
+    }
+}
+
+impl<W: Write> Observer for Csv<W> {
+    fn progress(&mut self, p: &Progress) -> io::Result<()> {
+        if !self.header_written {
+            writeln!(self.writer, "iterations,elapsed,rate,nodes,memory,mean_depth,max_depth,depths,best_value,temperature,schedule")?;
+            self.header_written = true;
+        }
+        let depths = p.depths.iter().map(|d| d.to_string()).collect::<Vec<_>>().join(";");
+        let best = p.best_value.map_or(String::new(), |v| v.to_string());
+        writeln!(self.writer, "{},{},{},{},{},{},{},{},{},{},{}", p.iterations, p.elapsed.as_secs_f64(), p.rate, p.nodes,
+                 p.memory, p.mean_depth(), p.max_depth(), depths, best, p.temperature, p.schedule)?;
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+use std::cell::RefCell;
+#[cfg(test)]
+use std::rc::Rc;
+
+/// Writes into a buffer that the test can still read.
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct Shared(Rc<RefCell<Vec<u8>>>);
+
+#[cfg(test)]
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl Shared {
+    fn text(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+#[test]
+fn test_progress() {
+    let p = Progress {
+        iterations: 10, elapsed: Duration::from_millis(1500), rate: 4.0, nodes: 7, memory: 1 << 20,
+        depths: vec![1, 2, 4], best_value: Some(2.5), temperature: 0.25, schedule: "constant:0.25".to_string(),
+    };
+    assert_eq!(p.max_depth(), 2);
+    assert!((p.mean_depth() - 10.0 / 7.0).abs() < 1e-12);
+
+    let (json, csv, status) = (Shared::default(), Shared::default(), Shared::default());
+    JsonLines(json.clone()).progress(&p).unwrap();
+    assert_eq!(json.text(), "{\"iterations\":10,\"elapsed\":1.5,\"rate\":4,\"nodes\":7,\"memory\":1048576,\
+                             \"depths\":[1,2,4],\"best_value\":2.5,\"temperature\":0.25,\"schedule\":\"constant:0.25\"}\n");
+    let odd = Shared::default();
+    JsonLines(odd.clone()).progress(&Progress { schedule: "a\"b\\c\n".to_string(), ..p.clone() }).unwrap();
+    assert!(odd.text().ends_with(",\"schedule\":\"a\\\"b\\\\c\\u000a\"}\n"), "{}", odd.text());
+    let mut sink = Csv::new(csv.clone());
+    sink.progress(&p).unwrap();
+    sink.progress(&Progress { best_value: None, ..p.clone() }).unwrap();
+    let text = csv.text();
+    let lines = text.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[1], format!("10,1.5,4,7,1048576,{},2,1;2;4,2.5,0.25,constant:0.25", 10.0 / 7.0));
+    assert!(lines[2].ends_with(",1;2;4,,0.25,constant:0.25"));
+    StatusLine(status.clone()).finish(&p).unwrap();
+    assert_eq!(status.text(), "\r\x1B[K10 iterations, 4/s, 7 nodes, 1.0 MB, depth 1.4 (max 2), best 2.50, T=0.2500 (constant:0.25)\n");
+}
+
+#[test]
+fn test_telemetry_reports_search() {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use treesearch::{Uniform, resume_treesearch};
+    let mut telemetry = Telemetry::new(Duration::from_secs(0));
+    assert!(!telemetry.is_due());
+    let json = Shared::default();
+    telemetry.add_observer(JsonLines(json.clone()));
+    let mut count = 0;
+    let mut game = ::game::Game::standard(7, 2, 3).unwrap();
+    let schedule = ::schedule::Linear { start: 1.0, rate: 0.5 };
+    let tree = resume_treesearch(&mut game, Uniform, None, &mut StdRng::seed_from_u64(1), &schedule, Tree::new(),
+                                 &mut telemetry, |_, _, _| {
+        count += 1;
+        count <= 20
+    });
+    let text = json.text();
+    // A report after each iteration, and a last one.
+    assert_eq!(text.lines().count(), 21);
+    let last = text.lines().last().unwrap();
+    assert!(last.starts_with("{\"iterations\":20,"));
+    assert!(last.contains(&format!("\"nodes\":{},", tree.node_count())));
+    assert!(last.ends_with(",\"temperature\":11,\"schedule\":\"linear:0.5:1\"}"));
+    assert!(!last.contains("\"best_value\":null"));
+}
+
+/// Fails every write, like a full disk.
+#[cfg(test)]
+struct Full;
+
+#[cfg(test)]
+impl Write for Full {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::Other, "disk full"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_failing_observer_is_dropped() {
+    let mut telemetry = Telemetry::new(Duration::from_secs(0));
+    let json = Shared::default();
+    telemetry.add_observer(Csv::new(Full));
+    telemetry.add_observer(JsonLines(json.clone()));
+    let (tree, schedule) = (Tree::new(), ::schedule::Constant(1.0));
+    telemetry.report(&tree, &schedule);
+    telemetry.finish(&tree, &schedule);
+    assert_eq!(telemetry.errors().len(), 1);
+    assert_eq!(telemetry.errors()[0].to_string(), "disk full");
+    assert_eq!(json.text().lines().count(), 2);
+}
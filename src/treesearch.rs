@@ -1,123 +1,505 @@
-use std::io::Write;
+use std::cmp::Ordering;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::time::Duration;
 
-use rand::Rng;
+use rand::{Rng, RngCore};
+
+use schedule::{Linear, Schedule};
+use telemetry::Telemetry;
 
 pub trait GameStateMut {
     fn undo(&mut self);
     fn move_count(&mut self) -> usize;
     fn select_move(&mut self, i: usize);
     fn value(&mut self) -> f64;
+    /// Estimate of `value` after the game is played out,
+    /// used when a playout is cut off early. It is averaged together with
+    /// `value` in the tree, so it must be in the same units.
+    fn heuristic(&mut self) -> f64 {
+        self.value()
+    }
+}
+
+/// Chooses the moves played during the random playout in `simulate`.
+pub trait PlayoutPolicy<G: ?Sized> {
+    /// Pick one of the `count` moves available in `game_state`.
+    fn choose(&mut self, game_state: &mut G, count: usize, rng: &mut dyn RngCore) -> usize;
+}
+
+/// Plays uniformly random moves.
+pub struct Uniform;
+
+impl<G: ?Sized> PlayoutPolicy<G> for Uniform {
+    fn choose(&mut self, _game_state: &mut G, count: usize, rng: &mut dyn RngCore) -> usize {
+        rng.gen_range(0, count)
+    }
 }
 
-struct TreeSearch<'a, G: GameStateMut> {
+impl<G: ?Sized, P: PlayoutPolicy<G> + ?Sized> PlayoutPolicy<G> for Box<P> {
+    fn choose(&mut self, game_state: &mut G, count: usize, rng: &mut dyn RngCore) -> usize {
+        (**self).choose(game_state, count, rng)
+    }
+}
+
+
+const NONE: u32 = u32::MAX;
+
+/// A node of `Tree`. Children are only created once they are visited; the
+/// other moves of an expanded node are unvisited leaves, valued at 0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Node {
+    /// `NONE` if the slot is free; the root is its own parent.
+    parent: u32,
+    /// Index of the move leading here among the moves of the parent.
+    move_index: u32,
+    first_child: u32,
+    /// The next child of the parent, or the next free slot.
+    next_sibling: u32,
+    /// Number of moves in this position, or `NONE` if the node is a leaf.
+    move_count: u32,
+    /// Number of created children.
+    children: u32,
+    visits: u64,
+    value_sum: f64,
+}
+
+impl Node {
+    fn new(parent: usize, move_index: usize) -> Self {
+        Node {
+            parent: parent as u32,
+            move_index: move_index as u32,
+            first_child: NONE,
+            next_sibling: NONE,
+            move_count: NONE,
+            children: 0,
+            visits: 0,
+            value_sum: 0.0,
+        }
+    }
+
+    fn is_free(&self) -> bool {
+        self.parent == NONE
+    }
+
+    fn mean(&self) -> f64 {
+        if self.visits > 0 { self.value_sum / self.visits as f64 } else { 0.0 }
+    }
+}
+
+/// The nodes of a search tree, which can be saved to continue the search later
+/// or rerooted to continue it after a move. Node 0 is the root.
+///
+/// With a memory budget, the subtrees below the nodes with the lowest average
+/// value are pruned whenever the budget is reached, turning those nodes back
+/// into leaves, and their slots are reused. The budget is not strict: if only
+/// leaves are left below the root, the tree grows beyond it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tree {
+    nodes: Vec<Node>,
+    /// The first free slot in `nodes`, or `NONE`.
+    free: u32,
+    /// Number of nodes in use.
+    live: usize,
+    /// Maximum number of nodes in use.
+    budget: Option<usize>,
+}
+
+const TREE_MAGIC: &[u8; 4] = b"BKTS";
+const TREE_VERSION: u32 = 3;
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+impl Tree {
+    /// A tree with only the unexplored root.
+    pub fn new() -> Self {
+        Tree { nodes: vec![Node::new(0, 0)], free: NONE, live: 1, budget: None }
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.live
+    }
+
+    /// Number of times the root has been visited.
+    pub fn visits(&self) -> usize {
+        self.nodes[0].visits as usize
+    }
+
+    /// Bytes taken by the nodes, including free slots.
+    pub fn memory(&self) -> usize {
+        self.nodes.len() * mem::size_of::<Node>()
+    }
+
+    /// Limit the nodes to about `bytes` of memory, or lift the limit.
+    pub fn set_memory_budget(&mut self, bytes: Option<usize>) {
+        self.budget = bytes.map(|b| (b / mem::size_of::<Node>()).max(8));
+    }
+
+    fn child_list(&self, i: usize) -> Vec<usize> {
+        let mut res = Vec::new();
+        let mut c = self.nodes[i].first_child;
+        while c != NONE {
+            res.push(c as usize);
+            c = self.nodes[c as usize].next_sibling;
+        }
+        res
+    }
+
+    fn child(&self, i: usize, move_index: usize) -> Option<usize> {
+        self.child_list(i).into_iter().find(|&c| self.nodes[c].move_index as usize == move_index)
+    }
+
+    /// A uniformly random move of expanded node `i` that has no child yet.
+    fn unvisited_move<R: Rng>(&self, i: usize, rng: &mut R) -> usize {
+        let node = &self.nodes[i];
+        let mut taken = self.child_list(i).iter().map(|&c| self.nodes[c].move_index as usize).collect::<Vec<_>>();
+        taken.sort();
+        let mut m = rng.gen_range(0, (node.move_count - node.children) as usize);
+        for t in taken {
+            if t <= m {
+                m += 1;
+            }
+        }
+        m
+    }
+
+    fn add_child(&mut self, parent: usize, move_index: usize) -> usize {
+        let mut node = Node::new(parent, move_index);
+        node.next_sibling = self.nodes[parent].first_child;
+        let i = if self.free == NONE {
+            // Slot `NONE` would read as a missing link.
+            assert!(self.nodes.len() < NONE as usize, "the search tree is out of slots; set a memory budget");
+            self.nodes.push(node);
+            self.nodes.len() - 1
+        } else {
+            let i = self.free as usize;
+            self.free = self.nodes[i].next_sibling;
+            self.nodes[i] = node;
+            i
+        };
+        self.nodes[parent].first_child = i as u32;
+        self.nodes[parent].children += 1;
+        self.live += 1;
+        i
+    }
+
+    /// Free the descendants of `i`, making it a leaf again; returns how many were freed.
+    fn collapse(&mut self, i: usize) -> usize {
+        let mut stack = self.child_list(i);
+        let node = &mut self.nodes[i];
+        node.first_child = NONE;
+        node.children = 0;
+        node.move_count = NONE;
+        let mut freed = 0;
+        while let Some(c) = stack.pop() {
+            stack.extend(self.child_list(c));
+            self.nodes[c].parent = NONE;
+            self.nodes[c].next_sibling = self.free;
+            self.free = c as u32;
+            freed += 1;
+        }
+        self.live -= freed;
+        freed
+    }
+
+    /// Collapse the nodes with the lowest average value until `target` nodes are freed.
+    fn prune(&mut self, target: usize) {
+        let mut candidates = (1..self.nodes.len())
+            .filter(|&i| !self.nodes[i].is_free() && self.nodes[i].first_child != NONE)
+            .collect::<Vec<_>>();
+        candidates.sort_by(|&a, &b| self.nodes[a].mean().partial_cmp(&self.nodes[b].mean()).unwrap_or(Ordering::Equal));
+        let mut freed = 0;
+        for i in candidates {
+            if freed >= target {
+                break;
+            }
+            if !self.nodes[i].is_free() && self.nodes[i].first_child != NONE {
+                freed += self.collapse(i);
+            }
+        }
+    }
+
+    /// Index of the root move with the best average value, among the visited ones.
+    pub fn best_move(&self) -> Option<usize> {
+        let mut children = self.child_list(0);
+        children.sort_by_key(|&c| self.nodes[c].move_index);
+        let mut best = None;
+        let mut best_value = f64::NEG_INFINITY;
+        for c in children {
+            let node = &self.nodes[c];
+            if node.visits > 0 && node.mean() > best_value {
+                best_value = node.mean();
+                best = Some(node.move_index as usize);
+            }
+        }
+        best
+    }
+
+    /// Keep only the subtree of root move `move_index`, as the tree of the position after that move.
+    pub fn reroot(&mut self, move_index: usize) {
+        let mut tree = Tree { budget: self.budget, ..Tree::new() };
+        if let Some(c) = self.child(0, move_index) {
+            tree.nodes[0] = Node { parent: 0, move_index: 0, first_child: NONE, next_sibling: NONE, children: 0, ..self.nodes[c] };
+            let mut stack = vec![(c, 0)];
+            while let Some((old, new)) = stack.pop() {
+                for child in self.child_list(old).into_iter().rev() {
+                    let i = tree.add_child(new, self.nodes[child].move_index as usize);
+                    let node = &self.nodes[child];
+                    tree.nodes[i].move_count = node.move_count;
+                    tree.nodes[i].visits = node.visits;
+                    tree.nodes[i].value_sum = node.value_sum;
+                    stack.push((child, i));
+                }
+            }
+        }
+        *self = tree;
+    }
+
+    /// Number of nodes at each depth, starting with the root at depth 0.
+    pub fn depth_histogram(&self) -> Vec<usize> {
+        let mut res = Vec::new();
+        let mut stack = vec![(0, 0)];
+        while let Some((i, depth)) = stack.pop() {
+            if res.len() <= depth {
+                res.resize(depth + 1, 0);
+            }
+            res[depth] += 1;
+            stack.extend(self.child_list(i).into_iter().map(|c| (c, depth + 1)));
+        }
+        res
+    }
+
+    /// Write the tree in little-endian binary: the magic `BKTS`, the `u32` version (3),
+    /// the `u64` number of slots and the `u32` first free slot, then for each slot
+    /// the `u32`s parent, move index, first child, next sibling, move count and
+    /// children, the `u64` visits and the `f64` value sum. `u32::MAX` stands for none.
+    pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(TREE_MAGIC)?;
+        w.write_all(&TREE_VERSION.to_le_bytes())?;
+        w.write_all(&(self.nodes.len() as u64).to_le_bytes())?;
+        w.write_all(&self.free.to_le_bytes())?;
+        for node in self.nodes.iter() {
+            for &x in [node.parent, node.move_index, node.first_child, node.next_sibling, node.move_count, node.children].iter() {
+                w.write_all(&x.to_le_bytes())?;
+            }
+            w.write_all(&node.visits.to_le_bytes())?;
+            w.write_all(&node.value_sum.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Read a tree written by `write`, without a memory budget.
+    pub fn read<R: Read>(r: &mut R) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != TREE_MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a search tree"));
+        }
+        if read_u32(r)? != TREE_VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "unsupported search tree version"));
+        }
+        let n = read_u64(r)?;
+        if n == 0 || n >= NONE as u64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid number of nodes"));
+        }
+        let free = read_u32(r)?;
+        let mut nodes = Vec::new();
+        for _ in 0..n {
+            let mut x = [0; 6];
+            for x in x.iter_mut() {
+                *x = read_u32(r)?;
+            }
+            let visits = read_u64(r)?;
+            let value_sum = f64::from_bits(read_u64(r)?);
+            nodes.push(Node {
+                parent: x[0], move_index: x[1], first_child: x[2], next_sibling: x[3],
+                move_count: x[4], children: x[5], visits, value_sum,
+            });
+        }
+        let live = nodes.iter().filter(|n| !n.is_free()).count();
+        let tree = Tree { nodes, free, live, budget: None };
+        tree.check().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(tree)
+    }
+
+    /// Whether the links between the nodes form a tree and a free list covering all slots.
+    fn check(&self) -> Result<(), &'static str> {
+        let n = self.nodes.len();
+        if self.nodes[0].parent != 0 {
+            return Err("invalid root");
+        }
+        let mut reached = 1;
+        let mut stack = vec![0];
+        while let Some(i) = stack.pop() {
+            let node = self.nodes[i];
+            let mut moves = Vec::new();
+            let mut c = node.first_child;
+            while c != NONE {
+                if c == 0 || c as usize >= n || self.nodes[c as usize].parent != i as u32 || moves.len() >= n {
+                    return Err("children and parents do not match");
+                }
+                moves.push(self.nodes[c as usize].move_index);
+                stack.push(c as usize);
+                c = self.nodes[c as usize].next_sibling;
+            }
+            reached += moves.len();
+            moves.sort();
+            moves.dedup();
+            let valid_moves = moves.iter().all(|&m| node.move_count != NONE && m < node.move_count);
+            if moves.len() != node.children as usize || !valid_moves {
+                return Err("invalid children");
+            }
+        }
+        let mut free = 0;
+        let mut f = self.free;
+        while f != NONE {
+            if f as usize >= n || !self.nodes[f as usize].is_free() || free >= n {
+                return Err("invalid free list");
+            }
+            free += 1;
+            f = self.nodes[f as usize].next_sibling;
+        }
+        if reached != self.live || reached + free != n {
+            return Err("unreachable nodes");
+        }
+        Ok(())
+    }
+}
+
+impl Default for Tree {
+    fn default() -> Self {
+        Tree::new()
+    }
+}
+
+struct TreeSearch<'a, G: GameStateMut, P: PlayoutPolicy<G>> {
     game_state: &'a mut G,
-    // children[i] is None if state is unexplored, or a vec of child indices otherwise.
-    children: Vec<Option<Vec<usize>>>,
-    // parent[0] is 0 (root special case)
-    parent: Vec<usize>,
-    value_sums: Vec<f64>,
-    value_counts: Vec<usize>,
+    policy: P,
+    /// Maximum number of moves in a playout before falling back to `heuristic`.
+    playout_depth: Option<usize>,
+    tree: Tree,
 }
 
-impl <'a, G: GameStateMut> TreeSearch<'a, G> {
-    fn select_leaf<R: Rng>(&mut self, rng: &mut R, temperature: f64) -> Option<usize> {
+/// A leaf of the tree: a node that has not been expanded, or an unvisited move of a node.
+enum Leaf {
+    Node(usize),
+    Unvisited(usize),
+}
+
+impl <'a, G: GameStateMut, P: PlayoutPolicy<G>> TreeSearch<'a, G, P> {
+    /// Choose a leaf with probability proportional to `exp(value * temperature)`.
+    /// An infinite temperature chooses among the best leaves, or the worst for
+    /// negative infinity, and so does a temperature that is not a number.
+    fn select_leaf<R: Rng>(&mut self, rng: &mut R, temperature: f64) -> Option<Leaf> {
+        // Nodes with the value and the number of leaves they stand for.
         let mut leaves = Vec::new();
-        let mut max = 0.0f64;
-        for i in 0..self.children.len() {
-            if self.children[i].is_some() {
+        let (mut min, mut max) = (f64::INFINITY, f64::NEG_INFINITY);
+        for (i, node) in self.tree.nodes.iter().enumerate() {
+            if node.is_free() {
                 continue;
             }
-            let value = if self.value_counts[i] > 0 {
-                self.value_sums[i] / self.value_counts[i] as f64
-            } else {
+            let value = if node.move_count == NONE {
+                leaves.push((i, node.mean(), 1));
+                node.mean()
+            } else if node.move_count > node.children {
+                leaves.push((i, 0.0, node.move_count - node.children));
                 0.0
+            } else {
+                continue;
             };
+            min = min.min(value);
             max = max.max(value);
-            leaves.push((i, value));
         }
         if leaves.is_empty() {
             return None;
         }
+        // Measure from the most likely value, so that no weight exceeds 1
+        // and the leaves with that value have weight 1.
+        let top = if temperature < 0.0 { min } else { max };
         let mut sum = 0.0;
-        for (_, ref mut value) in leaves.iter_mut() {
-            *value = ((*value - max) * temperature).exp();
-            sum += *value;
+        for (_, ref mut value, count) in leaves.iter_mut() {
+            *value = if temperature.is_finite() {
+                ((*value - top) * temperature).exp()
+            } else if *value == top {
+                1.0
+            } else {
+                0.0
+            };
+            sum += *value * *count as f64;
         }
-        assert!(sum > 1e-5);
+        assert!(sum >= 1.0);
         let v = rng.gen_range(0.0, sum);
         let mut acc = 0.0;
-        for &(i, value) in leaves.iter() {
-            acc += value;
+        for &(i, value, count) in leaves.iter() {
+            acc += value * count as f64;
             if v < acc {
-                print!("\r\x1B[KT={:.4} Selected {:8} with prob {:.4}", temperature, i, value / sum);
-                std::io::stdout().flush().ok().expect("Could not flush stdout");
-                return Some(i);
+                return Some(if self.tree.nodes[i].move_count == NONE { Leaf::Node(i) } else { Leaf::Unvisited(i) });
             }
         }
         panic!();
     }
 
-    fn from_root_to_node(&mut self, mut i: usize) {
+    fn descend_to_node(&mut self, mut i: usize) {
         let mut stack = Vec::new();
         while i > 0 {
-            stack.push(i);
-            i = self.parent[i];
+            stack.push(self.tree.nodes[i].move_index as usize);
+            i = self.tree.nodes[i].parent as usize;
         }
-        while let Some(i) = stack.pop() {
-            let p: usize = self.parent[i];
-            let mut j: usize = 0;
-            let c = self.children[p].as_ref().unwrap();
-            while j < c.len() && c[j] != i {
-                j += 1;
-            }
-            assert!(j < c.len());
+        while let Some(j) = stack.pop() {
             self.game_state.select_move(j);
         }
     }
 
-    fn from_node_to_root(&mut self, mut i: usize) {
+    fn ascend_to_root(&mut self, mut i: usize) {
         while i > 0 {
             self.game_state.undo();
-            i = self.parent[i];
+            i = self.tree.nodes[i].parent as usize;
         }
     }
 
     fn expand<R: Rng>(&mut self, i: usize, rng: &mut R) -> usize {
         // Assume state is leaf i, goes to and returns random new state
-        assert!(self.children[i].is_none());
+        assert_eq!(self.tree.nodes[i].move_count, NONE);
         let count = self.game_state.move_count();
-        let mut c = Vec::new();
-        for _ in 0..count {
-            c.push(self.children.len());
-            self.parent.push(i);
-            self.children.push(None);
-            self.value_sums.push(0.0);
-            self.value_counts.push(0);
-        }
+        self.tree.nodes[i].move_count = count as u32;
         if count > 0 {
             let j = rng.gen_range(0, count);
-            let res = c[j];
-            self.children[i] = Some(c);
+            let res = self.tree.add_child(i, j);
             self.game_state.select_move(j);
             res
         } else {
-            self.children[i] = Some(c);
             i
         }
     }
 
     fn simulate<R: Rng>(&mut self, rng: &mut R) -> f64 {
         let mut depth = 0;
+        let mut cut_off = false;
         loop {
             let count = self.game_state.move_count();
             if count == 0 {
                 break;
             }
+            if Some(depth) == self.playout_depth {
+                cut_off = true;
+                break;
+            }
             depth += 1;
-            let j = rng.gen_range(0, count);
+            let j = self.policy.choose(self.game_state, count, rng);
+            assert!(j < count);
             self.game_state.select_move(j);
         }
-        let res = self.game_state.value();
+        let res = if cut_off { self.game_state.heuristic() } else { self.game_state.value() };
         for _ in 0..depth {
             self.game_state.undo();
         }
@@ -126,36 +508,213 @@ impl <'a, G: GameStateMut> TreeSearch<'a, G> {
 
     fn backpropagation(&mut self, mut i: usize, val: f64) {
         loop {
-            self.value_sums[i] += val;
-            self.value_counts[i] += 1;
+            let node = &mut self.tree.nodes[i];
+            node.value_sum += val;
+            node.visits += 1;
             if i == 0 {
                 break;
             }
-            i = self.parent[i];
+            i = node.parent as usize;
+        }
+    }
+
+    /// Run one iteration of the search and return the value of the playout,
+    /// or `None` if the whole tree has been explored.
+    fn iterate<R: Rng>(&mut self, rng: &mut R, temperature: f64) -> Option<f64> {
+        if let Some(budget) = self.tree.budget {
+            // An iteration adds at most two nodes.
+            if self.tree.live + 2 > budget {
+                let target = self.tree.live - budget * 3 / 4;
+                self.tree.prune(target);
+            }
         }
+        let leaf = self.select_leaf(rng, temperature)?;
+        let i = match leaf {
+            Leaf::Node(i) => {
+                self.descend_to_node(i);
+                i
+            },
+            Leaf::Unvisited(p) => {
+                self.descend_to_node(p);
+                let m = self.tree.unvisited_move(p, rng);
+                self.game_state.select_move(m);
+                self.tree.add_child(p, m)
+            },
+        };
+        let j = self.expand(i, rng);
+        let val = self.simulate(rng);
+        self.backpropagation(j, val);
+        self.ascend_to_root(j);
+        Some(val)
     }
 }
 
-pub fn run_treesearch<G: GameStateMut, R: Rng>(game_state: &mut G, rng: &mut R, speed: f64) {
-    let mut t = TreeSearch {
-        game_state: game_state,
-        children: vec![None],
-        parent: vec![0],
-        value_sums: vec![0.0f64],
-        value_counts: vec![0],
-    };
+fn new_treesearch<G: GameStateMut, P: PlayoutPolicy<G>>(game_state: &mut G, policy: P, playout_depth: Option<usize>) -> TreeSearch<'_, G, P> {
+    TreeSearch {
+        game_state,
+        policy,
+        playout_depth,
+        tree: Tree::new(),
+    }
+}
 
-    let mut temperature = speed;
-    loop {
-        let i = match t.select_leaf(rng, temperature) {
-            Some(i) => i,
-            None => return,
-        };
-        t.from_root_to_node(i);
-        let j = t.expand(i, rng);
-        let val = t.simulate(rng);
-        t.backpropagation(j, val);
-        t.from_node_to_root(j);
-        temperature += speed;
+pub fn run_treesearch<G: GameStateMut, P: PlayoutPolicy<G>, R: Rng>(game_state: &mut G, policy: P, playout_depth: Option<usize>, rng: &mut R, speed: f64) {
+    let mut telemetry = Telemetry::new(Duration::from_secs(1));
+    let schedule = Linear { start: speed, rate: speed };
+    resume_treesearch(game_state, policy, playout_depth, rng, &schedule, Tree::new(), &mut telemetry, |_, _, _| true);
+}
+
+/// Continue the search of `run_treesearch` in `tree`, taking the temperature of
+/// each iteration from `schedule` by the number of iterations done, so that a
+/// resumed search picks up where it left off. Before each iteration, with the game
+/// at the root, `callback(game_state, tree, rng)` may save the search and returns
+/// whether to go on. Progress is reported to `telemetry`. Returns the tree when
+/// the callback stops the search or the whole tree has been explored.
+#[allow(clippy::too_many_arguments)]
+pub fn resume_treesearch<G, P, R, F>(game_state: &mut G, policy: P, playout_depth: Option<usize>, rng: &mut R,
+                                     schedule: &dyn Schedule, tree: Tree, telemetry: &mut Telemetry,
+                                     mut callback: F) -> Tree
+    where G: GameStateMut, P: PlayoutPolicy<G>, R: Rng, F: FnMut(&mut G, &Tree, &mut R) -> bool
+{
+    let mut t = new_treesearch(game_state, policy, playout_depth);
+    t.tree = tree;
+    while callback(t.game_state, &t.tree, rng) {
+        let temperature = schedule.temperature(t.tree.visits());
+        match t.iterate(rng, temperature) {
+            Some(value) => telemetry.record(value),
+            None => break,
+        }
+        if telemetry.is_due() {
+            telemetry.report(&t.tree, schedule);
+        }
+    }
+    telemetry.finish(&t.tree, schedule);
+    t.tree
+}
+
+/// Run the search for a fixed number of iterations at a fixed temperature
+/// and return the index of the root move with the best average value,
+/// or `None` if there are no moves.
+pub fn best_move<G: GameStateMut, P: PlayoutPolicy<G>, R: Rng>(game_state: &mut G, policy: P, playout_depth: Option<usize>, rng: &mut R, iterations: usize, temperature: f64) -> Option<usize> {
+    best_move_in(game_state, policy, playout_depth, rng, &mut Tree::new(), iterations, temperature)
+}
+
+/// Like `best_move`, but continue the search in `tree`, which belongs to the
+/// current position of `game_state`, e.g. after `Tree::reroot`.
+#[allow(clippy::too_many_arguments)]
+pub fn best_move_in<G: GameStateMut, P: PlayoutPolicy<G>, R: Rng>(game_state: &mut G, policy: P, playout_depth: Option<usize>, rng: &mut R,
+                                                                 tree: &mut Tree, iterations: usize, temperature: f64) -> Option<usize> {
+    let mut t = new_treesearch(game_state, policy, playout_depth);
+    t.tree = mem::take(tree);
+    for _ in 0..iterations {
+        if t.iterate(rng, temperature).is_none() {
+            break;
+        }
+    }
+    *tree = t.tree;
+    tree.best_move()
+}
+
+#[cfg(test)]
+fn search(game: &mut ::game::Game, tree: Tree, iterations: usize, seed: u64) -> Tree {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut count = 0;
+    let schedule = ::schedule::Constant(1.0);
+    let tree = resume_treesearch(game, Uniform, None, &mut rng, &schedule, tree, &mut Telemetry::new(Duration::from_secs(1)), |_, _, _| {
+        count += 1;
+        count <= iterations
+    });
+    tree.check().unwrap();
+    tree
+}
+
+#[test]
+fn test_lazy_children_and_pruning() {
+    let mut g = ::game::Game::standard(7, 2, 3).unwrap();
+    let tree = search(&mut g, Tree::new(), 200, 1);
+    assert_eq!(tree.visits(), 200);
+    // Each iteration creates a node and one of its children.
+    assert!(tree.node_count() <= 2 * 200 + 1);
+    assert!(g.history().is_empty());
+
+    let mut small = Tree::new();
+    small.set_memory_budget(Some(40 * mem::size_of::<Node>()));
+    let small = search(&mut g, small, 500, 1);
+    assert_eq!(small.visits(), 500);
+    assert!(small.node_count() <= 40);
+    assert!(small.memory() <= 40 * mem::size_of::<Node>());
+    assert!(small.free != NONE);
+
+    let mut bytes = Vec::new();
+    small.write(&mut bytes).unwrap();
+    let mut read = Tree::read(&mut &bytes[..]).unwrap();
+    read.set_memory_budget(Some(40 * mem::size_of::<Node>()));
+    assert_eq!(read, small);
+    assert!(Tree::read(&mut &bytes[..bytes.len() - 1]).is_err());
+    // Visit counts beyond the range of `u32` are kept.
+    let mut many = Tree::new();
+    many.nodes[0].visits = 1 << 40;
+    let mut many_bytes = Vec::new();
+    many.write(&mut many_bytes).unwrap();
+    assert_eq!(Tree::read(&mut &many_bytes[..]).unwrap().visits(), 1 << 40);
+    // Make the root its own child.
+    bytes[20 + 8..20 + 12].copy_from_slice(&0u32.to_le_bytes());
+    assert!(Tree::read(&mut &bytes[..]).is_err());
+}
+
+#[test]
+fn test_extreme_temperatures() {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use schedule::{Constant, Exponential};
+    // The exponential schedule overflows to infinity after about 1000 iterations.
+    let schedule = Exponential { start: 1.0, factor: 2.0 };
+    assert_eq!(schedule.temperature(1100), f64::INFINITY);
+    let mut g = ::game::Game::standard(7, 2, 3).unwrap();
+    let mut count = 0;
+    let tree = resume_treesearch(&mut g, Uniform, None, &mut StdRng::seed_from_u64(4), &schedule, Tree::new(),
+                                 &mut Telemetry::new(Duration::from_secs(1)), |_, _, _| {
+        count += 1;
+        count <= 1100
+    });
+    assert_eq!(tree.visits(), 1100);
+    for &temperature in &[f64::NEG_INFINITY, -1e300, 1e300, f64::NAN] {
+        let mut count = 0;
+        let schedule = Constant(temperature);
+        let tree = resume_treesearch(&mut g, Uniform, None, &mut StdRng::seed_from_u64(5), &schedule, Tree::new(),
+                                     &mut Telemetry::new(Duration::from_secs(1)), |_, _, _| {
+            count += 1;
+            count <= 50
+        });
+        assert_eq!(tree.visits(), 50);
     }
 }
+
+#[test]
+fn test_reroot() {
+    let mut g = ::game::Game::standard(7, 2, 3).unwrap();
+    let mut tree = search(&mut g, Tree::new(), 300, 2);
+    let m = tree.best_move().unwrap();
+    let c = tree.child(0, m).unwrap();
+    let (visits, value_sum) = (tree.nodes[c].visits, tree.nodes[c].value_sum);
+    let mut size = 0;
+    let mut stack = vec![c];
+    while let Some(i) = stack.pop() {
+        size += 1;
+        stack.extend(tree.child_list(i));
+    }
+    tree.reroot(m);
+    tree.check().unwrap();
+    assert_eq!(tree.node_count(), size);
+    assert_eq!((tree.nodes[0].visits, tree.nodes[0].value_sum), (visits, value_sum));
+
+    g.select_move(m);
+    let tree = search(&mut g, tree, 50, 3);
+    assert_eq!(tree.visits(), visits as usize + 50);
+    assert_eq!(g.history().len(), 1);
+    let mut empty = Tree::new();
+    empty.reroot(0);
+    assert_eq!(empty, Tree::new());
+}
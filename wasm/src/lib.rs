@@ -0,0 +1,134 @@
+//! JavaScript bindings for playing Blokus in a browser.
+//!
+//! Build with `wasm-pack build --target web wasm` and open `wasm/www/index.html`
+//! from a local web server. Moves are written in the notation of `blokus::notation`.
+//!
+//! `std::time` is not available on `wasm32-unknown-unknown`, so the time-limited
+//! search agents cannot be used here; `random`, `greedy` and `mcts:N` can.
+
+extern crate blokus;
+extern crate rand;
+extern crate wasm_bindgen;
+
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use wasm_bindgen::prelude::*;
+
+use blokus::agent::parse_agent;
+use blokus::game::Game;
+use blokus::notation::{format_move, parse_move};
+use blokus::treesearch::GameStateMut;
+
+#[wasm_bindgen]
+pub struct Blokus {
+    game: Game,
+    rng: StdRng,
+}
+
+#[wasm_bindgen]
+impl Blokus {
+    /// A new game on a `size` by `size` board where each of `colors` (2 to 8)
+    /// starts in a corner with all pieces of up to `piece_size` squares.
+    #[wasm_bindgen(constructor)]
+    pub fn new(size: usize, colors: usize, piece_size: usize, seed: u32) -> Result<Blokus, String> {
+        Ok(Blokus {
+            game: Game::standard(size, colors, piece_size).map_err(|e| e.to_string())?,
+            rng: StdRng::seed_from_u64(seed as u64),
+        })
+    }
+
+    pub fn rows(&self) -> usize {
+        self.game.board().rows()
+    }
+
+    pub fn cols(&self) -> usize {
+        self.game.board().cols()
+    }
+
+    pub fn colors(&self) -> usize {
+        self.game.board().colors()
+    }
+
+    /// The color of each cell in row-major order, or -1 for an empty cell.
+    pub fn cells(&self) -> Vec<i32> {
+        let board = self.game.board();
+        (0..board.size()).map(|i| board.at(i).map_or(-1, |c| c as i32)).collect()
+    }
+
+    /// The color to move, or -1 when the game is over.
+    #[wasm_bindgen(js_name = toMove)]
+    pub fn to_move(&mut self) -> i32 {
+        if self.game.move_count() == 0 { -1 } else { self.game.to_move() as i32 }
+    }
+
+    #[wasm_bindgen(js_name = legalMoves)]
+    pub fn legal_moves(&mut self) -> Vec<String> {
+        if self.game.move_count() == 0 {
+            return Vec::new();
+        }
+        if self.game.must_pass() {
+            return vec![format_move(self.game.board(), None)];
+        }
+        let moves = self.game.legal_moves().to_vec();
+        moves.into_iter().map(|m| format_move(self.game.board(), Some(m))).collect()
+    }
+
+    /// Play a move for the color to move, e.g. `a1,a2,b2` or `pass`.
+    pub fn play(&mut self, mv: &str) -> Result<(), String> {
+        if self.game.move_count() == 0 {
+            return Err("the game is over".to_string());
+        }
+        let color = self.game.to_move();
+        let mv = parse_move(self.game.board(), color, mv)?;
+        self.game.play(mv).map_err(|e| e.to_string())
+    }
+
+    /// Let a bot play a move for the color to move and return it.
+    #[wasm_bindgen(js_name = botMove)]
+    pub fn bot_move(&mut self, agent: &str) -> Result<String, String> {
+        if agent.starts_with("paranoid") || agent.starts_with("maxn") {
+            return Err(format!("agent {:?} needs a clock, which is not available here", agent));
+        }
+        let mut agent = parse_agent(agent)?;
+        if self.game.move_count() == 0 {
+            return Err("the game is over".to_string());
+        }
+        agent.check(self.game.board())?;
+        let mv = agent.choose(&mut self.game, &mut self.rng);
+        self.game.play(mv).map_err(|e| e.to_string())?;
+        Ok(format_move(self.game.board(), mv))
+    }
+
+    /// Take back the last move; false if there is nothing to take back.
+    pub fn undo(&mut self) -> bool {
+        self.game.take_back().is_some()
+    }
+
+    pub fn scores(&self) -> Vec<i32> {
+        self.game.scores()
+    }
+
+    pub fn history(&self) -> Vec<String> {
+        self.game.history().iter().map(|&mv| format_move(self.game.board(), mv)).collect()
+    }
+}
+
+#[test]
+fn test_bindings() {
+    let mut b = Blokus::new(5, 2, 2, 1).unwrap();
+    assert!(Blokus::new(5, 9, 2, 1).is_err());
+    assert_eq!(b.to_move(), 0);
+    assert!(b.legal_moves().contains(&"a1,b1".to_string()));
+    b.play("a1,b1").unwrap();
+    assert!(b.play("a2").is_err());
+    assert!(b.bot_move("paranoid:10").is_err());
+    let reply = b.bot_move("greedy").unwrap();
+    assert_eq!(b.history(), vec!["a1,b1".to_string(), reply]);
+    assert_eq!(b.cells()[..3], [0, 0, -1]);
+    assert!(b.undo());
+    while b.to_move() >= 0 {
+        b.bot_move("random").unwrap();
+    }
+    assert_eq!(b.scores().len(), 2);
+    assert!(b.legal_moves().is_empty());
+}
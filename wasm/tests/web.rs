@@ -0,0 +1,24 @@
+//! Headless tests in a wasm runtime: `cargo test --target wasm32-unknown-unknown`
+//! from this directory with `wasm-bindgen-test-runner` installed, or `wasm-pack test --node`.
+
+#![cfg(target_arch = "wasm32")]
+
+extern crate blokus_wasm;
+extern crate wasm_bindgen_test;
+
+use blokus_wasm::Blokus;
+use wasm_bindgen_test::*;
+
+#[wasm_bindgen_test]
+fn test_play_against_bots() {
+    let mut b = Blokus::new(8, 2, 4, 7).unwrap();
+    let first = b.legal_moves()[0].clone();
+    b.play(&first).unwrap();
+    b.bot_move("mcts:20").unwrap();
+    assert_eq!(b.history().len(), 2);
+    while b.to_move() >= 0 {
+        b.bot_move("greedy").unwrap();
+    }
+    assert_eq!(b.cells().len(), 64);
+    assert!(b.bot_move("paranoid:10").is_err());
+}